@@ -0,0 +1,240 @@
+use std::{
+    io::{self, BufRead, Write},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    pbf::format_timestamp,
+    symbol::{with_string_table, with_symbol_table, SymbolTable},
+    Bounds, Element, Error, Osm, Result, GENERATOR,
+};
+
+/// first bytes of every osm.bin, followed by the format version as a big endian u32, the CBOR
+/// encoded `Header`, the string table as a CBOR array of strings and the CBOR encoded `Osm` whose
+/// tag keys and values are indexes into the string table
+pub const MAGIC: &[u8; 8] = b"OSMTILES";
+/// bumped whenever the layout of `Header` or `Osm` changes incompatibly
+pub const FORMAT_VERSION: u32 = 2;
+/// files written before tag strings were interned have no string table, the tags are inline
+pub const INLINE_STRINGS_FORMAT_VERSION: u32 = 1;
+/// files written before the header was introduced are bare CBOR encoded `Osm`
+pub const LEGACY_FORMAT_VERSION: u32 = 0;
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Header {
+    /// name of the file the dataset was imported from
+    pub source: Option<String>,
+    pub imported_at: String,
+    /// osm-tiles version that wrote the file
+    pub written_by: String,
+    pub bounds: Option<Bounds>,
+    pub nodes: u64,
+    pub ways: u64,
+    pub relations: u64,
+}
+
+impl Header {
+    pub fn new(source: Option<String>) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs() as i64)
+            .unwrap_or_default();
+        Header {
+            source,
+            imported_at: format_timestamp(now),
+            written_by: GENERATOR.to_string(),
+            bounds: None,
+            nodes: 0,
+            ways: 0,
+            relations: 0,
+        }
+    }
+
+    pub fn count(&mut self, element: &Element) {
+        match element {
+            Element::Node(_) => self.nodes += 1,
+            Element::Way(_) => self.ways += 1,
+            Element::Relation(_) => self.relations += 1,
+        }
+    }
+
+    /// takes the counts and bounds from the dataset, the source and import time are kept
+    pub fn update(&mut self, osm: &Osm) {
+        self.written_by = GENERATOR.to_string();
+        self.bounds = osm.bounds.clone();
+        self.nodes = osm.node.len() as u64;
+        self.ways = osm.way.len() as u64;
+        self.relations = osm.relation.len() as u64;
+    }
+}
+
+fn corrupt(what: &str) -> impl Fn(ciborium::de::Error<io::Error>) -> Error + '_ {
+    move |error| match error {
+        ciborium::de::Error::Io(error) => Error::Io(error),
+        error => Error::Corrupt(format!("{}: {}", what, error)),
+    }
+}
+
+/// format version of the stream, only the magic and the version are consumed
+pub fn read_format_version<R: BufRead>(reader: &mut R) -> io::Result<u32> {
+    if !reader.fill_buf()?.starts_with(MAGIC) {
+        return Ok(LEGACY_FORMAT_VERSION);
+    }
+    reader.consume(MAGIC.len());
+    let mut version = [0u8; 4];
+    reader.read_exact(&mut version)?;
+    Ok(u32::from_be_bytes(version))
+}
+
+fn unsupported_version(version: u32) -> Error {
+    if version < FORMAT_VERSION {
+        Error::LegacyDataset
+    } else {
+        Error::UnsupportedVersion {
+            found: version,
+            expected: FORMAT_VERSION,
+        }
+    }
+}
+
+/// reads the header of a dataset, the reader is left at the start of the string table
+pub fn read_header<R: BufRead>(reader: &mut R) -> Result<Header> {
+    match read_format_version(reader)? {
+        FORMAT_VERSION => ciborium::from_reader(reader).map_err(corrupt("invalid header")),
+        version => Err(unsupported_version(version)),
+    }
+}
+
+/// reads the string table and the elements that follow the header
+fn read_elements<R: BufRead>(mut reader: R) -> Result<Osm> {
+    let table: Vec<String> =
+        ciborium::from_reader(&mut reader).map_err(corrupt("invalid string table"))?;
+    with_string_table(&table, || ciborium::from_reader(reader)).map_err(corrupt("invalid elements"))
+}
+
+pub fn read_binary<R: BufRead>(mut reader: R) -> Result<(Header, Osm)> {
+    let header = read_header(&mut reader)?;
+    let osm = read_elements(reader)?;
+    Ok((header, osm))
+}
+
+/// reads a dataset written with any known format version, converting it to the current one
+pub fn migrate<R: BufRead>(mut reader: R) -> Result<(Header, Osm)> {
+    match read_format_version(&mut reader)? {
+        LEGACY_FORMAT_VERSION => {
+            let osm: Osm =
+                ciborium::from_reader(reader).map_err(corrupt("invalid legacy elements"))?;
+            let mut header = Header::new(None);
+            header.update(&osm);
+            Ok((header, osm))
+        }
+        INLINE_STRINGS_FORMAT_VERSION => {
+            let header = ciborium::from_reader(&mut reader).map_err(corrupt("invalid header"))?;
+            let osm = ciborium::from_reader(reader).map_err(corrupt("invalid elements"))?;
+            Ok((header, osm))
+        }
+        FORMAT_VERSION => {
+            let header = ciborium::from_reader(&mut reader).map_err(corrupt("invalid header"))?;
+            let osm = read_elements(reader)?;
+            Ok((header, osm))
+        }
+        version => Err(unsupported_version(version)),
+    }
+}
+
+/// writes the magic, the format version, the header and the string table, the CBOR encoded
+/// `Osm` serialized with the table has to follow
+pub fn write_header<W: Write>(
+    writer: &mut W,
+    header: &Header,
+    table: &SymbolTable,
+) -> io::Result<()> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&FORMAT_VERSION.to_be_bytes())?;
+    ciborium::into_writer(header, &mut *writer)
+        .map_err(|error| io::Error::other(error.to_string()))?;
+    ciborium::into_writer(table.strings(), writer)
+        .map_err(|error| io::Error::other(error.to_string()))
+}
+
+/// the elements are serialized twice, first only to collect the strings of their tags for the
+/// string table that precedes them
+pub fn write_binary<W: Write>(mut writer: W, header: &Header, osm: &Osm) -> io::Result<W> {
+    let mut table = SymbolTable::default();
+    with_symbol_table(&mut table, || ciborium::into_writer(osm, io::sink()))
+        .map_err(|error| io::Error::other(error.to_string()))?;
+    write_header(&mut writer, header, &table)?;
+    with_symbol_table(&mut table, || ciborium::into_writer(osm, &mut writer))
+        .map_err(|error| io::Error::other(error.to_string()))?;
+    Ok(writer)
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use crate::{
+        stream::{ElementReader, XmlReader},
+        Osm, Tag,
+    };
+
+    use super::{migrate, read_binary, write_binary, Header, FORMAT_VERSION, MAGIC};
+
+    const OSM_XML: &str = r#"<osm version="0.6">
+  <bounds minlat="46.9" minlon="28.7" maxlat="47.1" maxlon="28.9"/>
+  <node id="1" lat="47.0" lon="28.8"/>
+  <node id="2" lat="47.1" lon="28.8"/>
+  <way id="10"><nd ref="1"/><nd ref="2"/></way>
+</osm>"#;
+
+    fn osm() -> Osm {
+        let mut reader = XmlReader::new(OSM_XML.as_bytes());
+        let mut osm = Osm::default();
+        reader
+            .by_ref()
+            .for_each(|element| osm.push(element.unwrap()));
+        osm.bounds = reader.bounds().cloned();
+        osm
+    }
+
+    #[test]
+    fn write_and_read_header() {
+        let mut osm = osm();
+        // tags are written as indexes into the string table
+        Arc::make_mut(&mut osm.way[0]).tag = Some(vec![Tag {
+            k: "highway".into(),
+            v: "residential".into(),
+        }]);
+        let mut header = Header::new(Some("moldova.osm.pbf".to_string()));
+        header.update(&osm);
+        let bytes = write_binary(Vec::new(), &header, &osm).unwrap();
+        assert!(bytes.starts_with(MAGIC));
+        assert_eq!(bytes[8..12], FORMAT_VERSION.to_be_bytes());
+
+        let (header, osm) = read_binary(bytes.as_slice()).unwrap();
+        assert_eq!(header.source.as_deref(), Some("moldova.osm.pbf"));
+        assert_eq!((header.nodes, header.ways, header.relations), (2, 1, 0));
+        assert_eq!(header.bounds.unwrap().maxlat, 47.1);
+        assert_eq!(osm.way[0].nd.len(), 2);
+        assert_eq!(osm.way[0].tag.as_ref().unwrap()[0].v, "residential");
+
+        let mut newer = bytes.clone();
+        newer[8..12].copy_from_slice(&(FORMAT_VERSION + 1).to_be_bytes());
+        assert!(read_binary(newer.as_slice()).is_err());
+    }
+
+    #[test]
+    fn migrate_legacy_dataset() {
+        let mut legacy = Vec::new();
+        ciborium::into_writer(&osm(), &mut legacy).unwrap();
+        let error = read_binary(legacy.as_slice()).err().unwrap();
+        assert!(error.to_string().contains("osm-tiles migrate"));
+
+        let (header, osm) = migrate(legacy.as_slice()).unwrap();
+        assert_eq!(header.source, None);
+        assert_eq!((header.nodes, header.ways), (2, 1));
+        assert_eq!(osm.node.len(), 2);
+    }
+}
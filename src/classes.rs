@@ -0,0 +1,395 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    ops::Index,
+    path::Path,
+    str::FromStr,
+    sync::OnceLock,
+};
+
+use serde::Deserialize;
+
+use crate::{Error, Result, Symbol, Tagged};
+
+/// classes compiled into osm-tiles, used when no other file is given
+const BUILTIN: &str = include_str!("../classes.toml");
+
+/// feature class of an element, an index into the `Classes` it was classified with
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct Class(usize);
+
+/// colour with components between 0 and 1, written as `[red, green, blue]` or
+/// `[red, green, blue, alpha]`
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize)]
+#[serde(try_from = "Vec<f64>")]
+pub struct Color {
+    pub red: f64,
+    pub green: f64,
+    pub blue: f64,
+    pub alpha: f64,
+}
+
+impl TryFrom<Vec<f64>> for Color {
+    type Error = String;
+
+    fn try_from(value: Vec<f64>) -> std::result::Result<Self, Self::Error> {
+        let (red, green, blue, alpha) = match value[..] {
+            [red, green, blue] => (red, green, blue, 1.0),
+            [red, green, blue, alpha] => (red, green, blue, alpha),
+            _ => {
+                return Err(format!(
+                    "a colour has 3 or 4 components, not {}",
+                    value.len()
+                ))
+            }
+        };
+        Ok(Color {
+            red,
+            green,
+            blue,
+            alpha,
+        })
+    }
+}
+
+/// how the elements of a class are drawn
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Style {
+    /// areas are filled with the colour
+    pub fill: Option<Color>,
+    /// ways and the outlines of areas are stroked with the colour
+    pub stroke: Option<Color>,
+    /// wider line in the colour drawn below the lines of all the classes
+    pub casing: Option<Color>,
+    /// line width in pixels, at zoom 16 when `scale_width` is set
+    pub width: f64,
+    /// the width is halved every two zoom levels below 16
+    pub scale_width: bool,
+    /// drawn with white dashes over the line, as railways
+    pub dashed: bool,
+    /// lower zoom levels do not draw the class
+    pub min_zoom: i32,
+    /// key of the tag written inside of areas
+    pub label: Option<String>,
+    pub label_min_zoom: i32,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            fill: None,
+            stroke: None,
+            casing: None,
+            width: 1.0,
+            scale_width: false,
+            dashed: false,
+            min_zoom: 0,
+            label: None,
+            label_min_zoom: 0,
+        }
+    }
+}
+
+impl Style {
+    /// line width at the zoom level
+    pub fn line_width(&self, z: i32) -> f64 {
+        if self.scale_width {
+            (self.width * 2f64.powf(f64::from(z - 16) / 2.0)).max(0.5)
+        } else {
+            self.width
+        }
+    }
+}
+
+/// tags that all have to be present, a key without values matches any value
+#[derive(Clone, Debug)]
+struct Rule(Vec<(Symbol, Option<Vec<Symbol>>)>);
+
+impl Rule {
+    fn matches(&self, element: &impl Tagged) -> bool {
+        self.0.iter().all(|(key, values)| {
+            element
+                .tag(key)
+                .is_some_and(|value| values.as_ref().is_none_or(|values| values.contains(&value)))
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FeatureClass {
+    pub name: String,
+    /// classes are drawn in order of priority, higher over lower
+    pub priority: i32,
+    pub style: Style,
+    /// the element belongs to the class when any of the rules matches
+    rules: Vec<Rule>,
+}
+
+impl FeatureClass {
+    pub fn matches(&self, element: &impl Tagged) -> bool {
+        self.rules.iter().any(|rule| rule.matches(element))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Values {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ClassConfig {
+    name: String,
+    #[serde(default)]
+    priority: i32,
+    #[serde(default, rename = "match")]
+    rules: Vec<HashMap<String, Values>>,
+    #[serde(default)]
+    style: Style,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    fallback: Option<String>,
+    #[serde(default)]
+    class: Vec<ClassConfig>,
+}
+
+/// feature classes read from a TOML file. An element belongs to every class with a matching rule,
+/// or to the fallback class when none matches
+#[derive(Clone, Debug)]
+pub struct Classes {
+    classes: Vec<FeatureClass>,
+    fallback: Option<Class>,
+    drawing_order: Vec<Class>,
+}
+
+impl Classes {
+    pub fn builtin() -> Self {
+        BUILTIN.parse().expect("the built-in classes are valid")
+    }
+
+    pub fn open(path: &Path) -> Result<Self> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// classes of the element, in drawing order
+    pub fn classify(&self, element: &impl Tagged) -> Vec<Class> {
+        let classes: Vec<Class> = self
+            .drawing_order
+            .iter()
+            .copied()
+            .filter(|&class| self[class].matches(element))
+            .collect();
+        if classes.is_empty() {
+            self.fallback.into_iter().collect()
+        } else {
+            classes
+        }
+    }
+
+    /// all the classes, from the lowest to the highest priority
+    pub fn drawing_order(&self) -> &[Class] {
+        &self.drawing_order
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<Class> {
+        self.classes
+            .iter()
+            .position(|class| class.name == name)
+            .map(Class)
+    }
+}
+
+impl Index<Class> for Classes {
+    type Output = FeatureClass;
+
+    fn index(&self, class: Class) -> &FeatureClass {
+        &self.classes[class.0]
+    }
+}
+
+impl FromStr for Classes {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let config: Config =
+            toml::from_str(s).map_err(|error| Error::InvalidClasses(error.to_string()))?;
+
+        let mut names = HashSet::new();
+        let classes: Vec<FeatureClass> = config
+            .class
+            .into_iter()
+            .map(|class| {
+                if !names.insert(class.name.clone()) {
+                    return Err(Error::InvalidClasses(format!(
+                        "class {} is defined twice",
+                        class.name
+                    )));
+                }
+                let rules = class
+                    .rules
+                    .into_iter()
+                    .map(|rule| {
+                        let tags = rule
+                            .into_iter()
+                            .map(|(key, values)| {
+                                let values = match values {
+                                    Values::One(value) if value == "*" => None,
+                                    Values::One(value) => Some(vec![Symbol::new(&value)]),
+                                    Values::Many(values) => Some(
+                                        values.iter().map(|value| Symbol::new(value)).collect(),
+                                    ),
+                                };
+                                (Symbol::new(&key), values)
+                            })
+                            .collect();
+                        Rule(tags)
+                    })
+                    .collect();
+                Ok(FeatureClass {
+                    name: class.name,
+                    priority: class.priority,
+                    style: class.style,
+                    rules,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        let mut classes = Classes {
+            drawing_order: (0..classes.len()).map(Class).collect(),
+            classes,
+            fallback: None,
+        };
+        classes.fallback = match &config.fallback {
+            Some(name) => Some(classes.by_name(name).ok_or_else(|| {
+                Error::InvalidClasses(format!("fallback class {} is not defined", name))
+            })?),
+            None => None,
+        };
+        // the sort is stable, classes with the same priority are drawn in file order
+        let priorities: Vec<i32> = classes.classes.iter().map(|class| class.priority).collect();
+        classes
+            .drawing_order
+            .sort_by_key(|class| priorities[class.0]);
+        Ok(classes)
+    }
+}
+
+static REGISTRY: OnceLock<Classes> = OnceLock::new();
+
+/// classes the elements are classified with, the built-in ones unless others were installed
+pub fn registry() -> &'static Classes {
+    REGISTRY.get_or_init(Classes::builtin)
+}
+
+/// replaces the built-in classes, before the first element is classified
+pub fn install(classes: Classes) {
+    if REGISTRY.set(classes).is_err() {
+        panic!("feature classes installed after elements were classified");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Tag, Way};
+
+    use super::{Classes, Color};
+
+    fn way(tags: &[(&str, &str)]) -> Way {
+        Way {
+            tag: Some(
+                tags.iter()
+                    .map(|(k, v)| Tag {
+                        k: (*k).into(),
+                        v: (*v).into(),
+                    })
+                    .collect(),
+            ),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn classify_elements() {
+        let classes: Classes = r#"
+            fallback = "other"
+
+            [[class]]
+            name = "building"
+            priority = 20
+            match = [{ building = "*" }]
+            style = { fill = [0.5, 0.5, 0.5, 0.2], label = "addr:housenumber" }
+
+            [[class]]
+            name = "park"
+            priority = 10
+            match = [{ leisure = "park" }, { landuse = ["grass", "village_green"] }]
+
+            [[class]]
+            name = "other"
+        "#
+        .parse()
+        .unwrap();
+        let names = |way: &Way| -> Vec<&str> {
+            classes
+                .classify(way)
+                .into_iter()
+                .map(|class| classes[class].name.as_str())
+                .collect()
+        };
+
+        assert_eq!(names(&way(&[("building", "house")])), vec!["building"]);
+        assert_eq!(names(&way(&[("landuse", "grass")])), vec!["park"]);
+        assert_eq!(
+            names(&way(&[("building", "yes"), ("leisure", "park")])),
+            vec!["park", "building"]
+        );
+        assert_eq!(names(&way(&[("landuse", "forest")])), vec!["other"]);
+        let building = &classes[classes.by_name("building").unwrap()].style;
+        assert_eq!(
+            building.fill,
+            Some(Color {
+                red: 0.5,
+                green: 0.5,
+                blue: 0.5,
+                alpha: 0.2
+            })
+        );
+        assert_eq!(building.width, 1.0);
+
+        assert!("fallback = \"none\"".parse::<Classes>().is_err());
+        assert!("[[class]]\nname = \"a\"\n[[class]]\nname = \"a\""
+            .parse::<Classes>()
+            .is_err());
+        assert!("[[class]]\nname = \"a\"\nstyle = { fill = [1.0] }"
+            .parse::<Classes>()
+            .is_err());
+    }
+
+    #[test]
+    fn builtin_classes() {
+        let classes = Classes::builtin();
+        let names = |way: &Way| -> Vec<&str> {
+            classes
+                .classify(way)
+                .into_iter()
+                .map(|class| classes[class].name.as_str())
+                .collect()
+        };
+        assert_eq!(names(&way(&[("highway", "primary_link")])), vec!["primary"]);
+        assert_eq!(names(&way(&[("highway", "proposed")])), vec!["other"]);
+        assert_eq!(names(&way(&[("railway", "abandoned")])), vec!["other"]);
+
+        let motorway = &classes[classes.by_name("motorway").unwrap()].style;
+        assert_eq!(motorway.line_width(16), 9.0);
+        assert_eq!(motorway.line_width(14), 4.5);
+        assert_eq!(motorway.line_width(2), 0.5);
+        let river = &classes[classes.by_name("river").unwrap()].style;
+        assert_eq!(river.line_width(2), 3.0);
+    }
+}
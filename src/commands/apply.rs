@@ -0,0 +1,56 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use log::info;
+use osm_tiles::{
+    binary::{read_binary, write_binary},
+    compression::{create_output, open_input, Compression},
+    osc::{apply_changes, expire_tiles, read_osc},
+    Result,
+};
+
+#[derive(Args)]
+pub struct ApplyArgs {
+    /// OsmChange (.osc) file, optionally compressed
+    change: PathBuf,
+    /// dataset the changes are applied to
+    #[arg(long, default_value = "osm.bin")]
+    data: PathBuf,
+    /// updated dataset, defaults to overwriting the input
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// tile cache the affected tiles are removed from
+    #[arg(long, default_value = "./cached")]
+    cache_dir: PathBuf,
+    #[arg(long, default_value_t = 0)]
+    min_zoom: u8,
+    #[arg(long, default_value_t = 19)]
+    max_zoom: u8,
+}
+
+pub fn run(args: ApplyArgs) -> Result<()> {
+    let output_path = args.output.unwrap_or(args.data.clone());
+
+    let (mut header, mut osm) = read_binary(open_input(&args.data)?)?;
+    let changes = read_osc(open_input(&args.change)?)?;
+    info!(
+        "applying {} changes from {}",
+        changes.len(),
+        args.change.display()
+    );
+
+    let tiles = apply_changes(&mut osm, changes, args.min_zoom..=args.max_zoom);
+
+    header.update(&osm);
+    let output = create_output(&output_path, Compression::from_path(&output_path))?;
+    write_binary(output, &header, &osm)?.finish()?;
+
+    tiles.iter().for_each(|(zoom, zoom_tiles)| {
+        zoom_tiles
+            .iter()
+            .for_each(|(x, y)| println!("{}/{}/{}", zoom, x, y));
+    });
+    let removed = expire_tiles(&args.cache_dir, &tiles)?;
+    info!("expired {} cached tiles", removed);
+    Ok(())
+}
@@ -0,0 +1,379 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use clap::{Args, ValueEnum};
+use log::info;
+use osm_tiles::{
+    binary::{write_header, Header},
+    compression::{create_output, CompressedWriter, Compression},
+    dataset::MemoryDataset,
+    extract::{clip_osm, filter_osm, Region, Selection, Strategy},
+    geojson::GeoJsonWriter,
+    input::{read_elements, read_osm, InputFormat},
+    json::JsonWriter,
+    stream::{CborWriter, XmlWriter},
+    Bounds, Element, ElementId, Error, Filter, MemberType, Osm, Relation, Result,
+};
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    /// OSM XML
+    Xml,
+    /// dataset the server loads, as written by `import`
+    Cbor,
+    /// OSM JSON as returned by the OSM API and Overpass
+    Json,
+    /// FeatureCollection of the tagged elements with their assembled geometries
+    Geojson,
+}
+
+impl OutputFormat {
+    /// format matching the extension of the file as for inputs, `.bin`, `.json` or `.geojson`,
+    /// other files are written as XML
+    fn from_path(path: &Path) -> Self {
+        match InputFormat::from_path(path) {
+            InputFormat::Cbor => OutputFormat::Cbor,
+            InputFormat::Json => OutputFormat::Json,
+            InputFormat::GeoJson => OutputFormat::Geojson,
+            _ => OutputFormat::Xml,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ExtractStrategy {
+    /// ways keep all their nodes and relations all their members, also outside of the region
+    Complete,
+    /// nodes outside of the region are dropped, ways lose the references to them
+    Clipped,
+}
+
+#[derive(Args)]
+pub struct ExtractArgs {
+    /// OSM XML, PBF or JSON file to extract from
+    input: PathBuf,
+    /// file the extract is written to, optionally compressed (.gz, .bz2, .zst)
+    #[arg(short, long, default_value = "temp.xml")]
+    output: PathBuf,
+    /// format of the output, by default picked from its extension
+    #[arg(long, value_enum)]
+    format: Option<OutputFormat>,
+    /// filter the elements are selected with, as `relation[leisure=park][name~"^Parcul"]`.
+    /// Conditions are `[key]`, `[!key]`, `[key=value]`, `[key!=value]`, `[key~regex]`,
+    /// `[key!~regex]` and numeric comparisons as `[lanes>=2]`, selectors separated by commas
+    /// match elements matching any of them. Ways are extracted with their nodes and relations
+    /// with their members, nested relations included. Everything in the region when left out
+    #[arg(short, long, required_unless_present_any = ["bbox", "boundary"])]
+    filter: Option<Filter>,
+    /// extracts the elements inside of `minlat,minlon,maxlat,maxlon`
+    #[arg(long, value_parser = parse_bbox, conflicts_with = "boundary")]
+    bbox: Option<Bounds>,
+    /// extracts the elements inside of the polygon of the boundary relation with the id
+    #[arg(long)]
+    boundary: Option<u64>,
+    /// how elements crossing the border of the region are extracted
+    #[arg(long, value_enum, default_value = "complete")]
+    strategy: ExtractStrategy,
+}
+
+pub(crate) fn parse_bbox(value: &str) -> std::result::Result<Bounds, String> {
+    let coordinates = value
+        .split(',')
+        .map(|coordinate| coordinate.trim().parse::<f64>())
+        .collect::<std::result::Result<Vec<f64>, _>>()
+        .map_err(|error| error.to_string())?;
+    match coordinates[..] {
+        [minlat, minlon, maxlat, maxlon] if minlat <= maxlat && minlon <= maxlon => Ok(Bounds {
+            minlat,
+            minlon,
+            maxlat,
+            maxlon,
+        }),
+        [_, _, _, _] => Err("the minimum is larger than the maximum".to_string()),
+        _ => Err("expected minlat,minlon,maxlat,maxlon".to_string()),
+    }
+}
+
+/// elements of one pass over a streamed input
+fn read_all(input: &Path) -> Result<impl Iterator<Item = Result<Element>>> {
+    Ok(read_elements(input)?.map(|element| element.map_err(Error::from)))
+}
+
+fn missing_boundary(input: &Path, relation_id: u64) -> Error {
+    Error::MissingElement {
+        element: ElementId::Relation(relation_id),
+        input: input.display().to_string(),
+    }
+}
+
+/// boundary relation of a streamed input, its ways and their nodes are read in two more passes
+fn streamed_boundary_region(input: &Path, relation_id: u64) -> Result<Region> {
+    let mut boundary_relation = None;
+    for element in read_all(input)? {
+        match element? {
+            Element::Relation(relation) if relation.id == relation_id => {
+                boundary_relation = Some(relation);
+                break;
+            }
+            _ => {}
+        }
+    }
+    let relation = boundary_relation.ok_or_else(|| missing_boundary(input, relation_id))?;
+    let way_ids: HashSet<u64> = relation
+        .member
+        .iter()
+        .filter(|member| member.member_type == MemberType::Way)
+        .map(|member| member.member_ref)
+        .collect();
+
+    let mut boundary = Osm::default();
+    let mut node_ids = HashSet::new();
+    for element in read_all(input)? {
+        if let Element::Way(way) = element? {
+            if way_ids.contains(&way.id) {
+                node_ids.extend(way.nd.iter().map(|nd| nd.reference));
+                boundary.push(Element::Way(way));
+            }
+        }
+    }
+    for element in read_all(input)? {
+        if let Element::Node(node) = element? {
+            if node_ids.contains(&node.id) {
+                boundary.push(Element::Node(node));
+            }
+        }
+    }
+
+    Region::boundary(&relation, &MemoryDataset::from(&boundary))
+}
+
+/// streams the extract in the output format, datasets are written in the layout of `import`
+enum ExtractWriter {
+    Xml(XmlWriter<CompressedWriter>),
+    Json(JsonWriter<CompressedWriter>),
+    GeoJson(GeoJsonWriter<CompressedWriter>),
+    Dataset {
+        writer: CborWriter<BufWriter<File>>,
+        header: Header,
+        output: PathBuf,
+        body_path: PathBuf,
+    },
+}
+
+impl ExtractWriter {
+    fn create(
+        input: &Path,
+        output: &Path,
+        format: OutputFormat,
+        bounds: Option<&Bounds>,
+    ) -> io::Result<Self> {
+        let compressed = || create_output(output, Compression::from_path(output));
+        Ok(match format {
+            OutputFormat::Xml => ExtractWriter::Xml(XmlWriter::new(compressed()?, bounds)?),
+            OutputFormat::Json => ExtractWriter::Json(JsonWriter::new(compressed()?, bounds)?),
+            OutputFormat::Geojson => {
+                ExtractWriter::GeoJson(GeoJsonWriter::new(compressed()?, bounds)?)
+            }
+            OutputFormat::Cbor => {
+                let mut header = Header::new(
+                    input
+                        .file_name()
+                        .map(|name| name.to_string_lossy().to_string()),
+                );
+                header.bounds = bounds.cloned();
+                let body_path = PathBuf::from(format!("{}.tmp", output.display()));
+                ExtractWriter::Dataset {
+                    writer: CborWriter::new(BufWriter::new(File::create(&body_path)?))?,
+                    header,
+                    output: output.to_path_buf(),
+                    body_path,
+                }
+            }
+        })
+    }
+
+    fn write(&mut self, element: &Element) -> io::Result<()> {
+        match self {
+            ExtractWriter::Xml(writer) => writer.write(element),
+            ExtractWriter::Json(writer) => writer.write(element),
+            ExtractWriter::GeoJson(writer) => writer.write(element),
+            ExtractWriter::Dataset { writer, header, .. } => {
+                header.count(element);
+                writer.write(element)
+            }
+        }
+    }
+
+    /// the counts of the dataset header are known at the end, the elements written to a
+    /// temporary file are appended to it
+    fn finish(self) -> io::Result<()> {
+        match self {
+            ExtractWriter::Xml(writer) => writer.finish()?.finish(),
+            ExtractWriter::Json(writer) => writer.finish()?.finish(),
+            ExtractWriter::GeoJson(writer) => writer.finish()?.finish(),
+            ExtractWriter::Dataset {
+                writer,
+                header,
+                output,
+                body_path,
+            } => {
+                let (mut body, table) = writer.finish(header.bounds.as_ref(), None)?;
+                body.flush()?;
+                let mut dataset = create_output(&output, Compression::from_path(&output))?;
+                write_header(&mut dataset, &header, &table)?;
+                io::copy(&mut File::open(&body_path)?, &mut dataset)?;
+                dataset.finish()?;
+                info!(
+                    "extracted {} nodes, {} ways and {} relations",
+                    header.nodes, header.ways, header.relations
+                );
+                fs::remove_file(&body_path)
+            }
+        }
+    }
+}
+
+pub fn run(args: ExtractArgs) -> Result<()> {
+    let filter = args.filter.clone().unwrap_or_else(|| "*".parse().unwrap());
+    let strategy = match args.strategy {
+        ExtractStrategy::Complete => Strategy::Complete,
+        ExtractStrategy::Clipped => Strategy::Clipped,
+    };
+    let format = args
+        .format
+        .unwrap_or_else(|| OutputFormat::from_path(&args.output));
+    if InputFormat::from_path(&args.input).is_streamed() {
+        extract_streamed(&args, &filter, strategy, format)
+    } else {
+        extract_loaded(&args, &filter, strategy, format)
+    }
+}
+
+/// datasets and JSON documents are loaded whole anyway, they are read once and extracted in
+/// memory
+fn extract_loaded(
+    args: &ExtractArgs,
+    filter: &Filter,
+    strategy: Strategy,
+    format: OutputFormat,
+) -> Result<()> {
+    let extract = {
+        let osm = read_osm(&args.input)?;
+        let region = match (&args.bbox, args.boundary) {
+            (Some(bounds), _) => Some(Region::Bounds(bounds.clone())),
+            (None, Some(relation_id)) => {
+                let Some(relation) = osm
+                    .relation
+                    .iter()
+                    .find(|relation| relation.id == relation_id)
+                else {
+                    return Err(missing_boundary(&args.input, relation_id));
+                };
+                Some(Region::boundary(relation, &MemoryDataset::from(&osm))?)
+            }
+            (None, None) => None,
+        };
+        match region {
+            Some(region) => clip_osm(&osm, filter, region, strategy),
+            None => filter_osm(&osm, filter),
+        }
+    };
+
+    let mut writer =
+        ExtractWriter::create(&args.input, &args.output, format, extract.bounds.as_ref())?;
+    for element in extract.into_elements() {
+        writer.write(&element)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// XML and PBF files are streamed once per pass, so that they never have to fit in memory
+fn extract_streamed(
+    args: &ExtractArgs,
+    filter: &Filter,
+    strategy: Strategy,
+    format: OutputFormat,
+) -> Result<()> {
+    let region = match (&args.bbox, args.boundary) {
+        (Some(bounds), _) => Some(Region::Bounds(bounds.clone())),
+        (None, Some(relation_id)) => Some(streamed_boundary_region(&args.input, relation_id)?),
+        (None, None) => None,
+    };
+    let bounds = region.as_ref().map(Region::bounds);
+    let mut selection = match region {
+        Some(region) => Selection::within(region, strategy),
+        None => Selection::default(),
+    };
+
+    // nodes come before the ways in the input, so that a single pass locates both of them in
+    // the region
+    if selection.has_region() {
+        for element in read_all(&args.input)? {
+            match element? {
+                Element::Node(node) => selection.locate_node(&node),
+                Element::Way(way) => selection.locate_way(&way),
+                Element::Relation(_) => {}
+            }
+        }
+    }
+
+    // the input is streamed once per element type: relations first, then ways and finally
+    // nodes, so that the elements referenced by the selected ones are known when their type is
+    // read. All relations are kept in memory so that the relations nested in a matching one can
+    // be resolved and extracted along with it
+    let mut id_to_relations: HashMap<u64, Arc<Relation>> = HashMap::new();
+    if filter.selects(MemberType::Relation) {
+        for element in read_all(&args.input)? {
+            if let Element::Relation(relation) = element? {
+                id_to_relations.insert(relation.id, Arc::new(relation));
+            }
+        }
+    }
+    selection.locate_relations(&id_to_relations);
+    selection.select_relations(filter, &id_to_relations);
+
+    let mut filtered_ways = Vec::new();
+    for element in read_all(&args.input)? {
+        if let Element::Way(mut way) = element? {
+            if selection.select_way(filter, &way) {
+                if selection.clips() {
+                    selection.clip_way(&mut way);
+                }
+                filtered_ways.push(Element::Way(way));
+            }
+        }
+    }
+
+    let mut writer = ExtractWriter::create(&args.input, &args.output, format, bounds.as_ref())?;
+    for element in read_all(&args.input)? {
+        if let Element::Node(node) = element? {
+            if selection.select_node(filter, &node) {
+                writer.write(&Element::Node(node))?;
+            }
+        }
+    }
+    for element in &filtered_ways {
+        writer.write(element)?;
+    }
+    let mut filtered_relations: Vec<u64> = selection.relations.iter().copied().collect();
+    filtered_relations.sort();
+    for relation_id in filtered_relations {
+        // selected relations are the ones read above
+        let Some(relation) = id_to_relations.remove(&relation_id) else {
+            continue;
+        };
+        let mut relation = Arc::unwrap_or_clone(relation);
+        if selection.clips() {
+            selection.clip_relation(&mut relation);
+        }
+        writer.write(&Element::Relation(relation))?;
+    }
+    writer.finish()?;
+    Ok(())
+}
@@ -0,0 +1,175 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+};
+
+use clap::{Args, ValueEnum};
+use log::{debug, info, warn};
+use osm_tiles::{
+    binary::{write_header, Header},
+    compression::{create_output, open_input, Compression},
+    geojson::read_geojson,
+    input::{read_elements, InputFormat},
+    mapped::MappedWriter,
+    merge::Merge,
+    stream::{CborWriter, ElementReader, MemoryReader},
+    validate::{summary, ValidatingReader},
+    Error, Result,
+};
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OutputCompression {
+    None,
+    Gzip,
+    Bzip2,
+    Zstd,
+}
+
+#[derive(Args)]
+pub struct ImportArgs {
+    /// OSM XML, PBF, JSON, GeoJSON or dataset files, optionally compressed. Several inputs are
+    /// merged, elements present in more than one of them are kept once with their highest version
+    #[arg(required = true)]
+    inputs: Vec<PathBuf>,
+    /// dataset loaded by the tile server, a `.map` file is written in the memory-mapped layout
+    #[arg(short, long, default_value = "osm.bin")]
+    output: PathBuf,
+    /// compression of the output, by default picked from the extension (.gz, .bz2, .zst)
+    #[arg(long, value_enum)]
+    compression: Option<OutputCompression>,
+}
+
+impl ImportArgs {
+    /// compression given on the command line, or the one of the output extension
+    fn compression(&self) -> Compression {
+        match self.compression {
+            Some(OutputCompression::None) => Compression::None,
+            Some(OutputCompression::Gzip) => Compression::Gzip,
+            Some(OutputCompression::Bzip2) => Compression::Bzip2,
+            Some(OutputCompression::Zstd) => Compression::Zstd,
+            None => Compression::from_path(&self.output),
+        }
+    }
+}
+
+pub fn run(args: ImportArgs) -> Result<()> {
+    // the memory-mapped layout is mapped as it is, it can not be compressed
+    let mapped = InputFormat::from_path(&args.output) == InputFormat::Mapped;
+    let compression = args.compression();
+    if mapped && compression != Compression::None {
+        return Err(Error::CompressedMappedDataset);
+    }
+
+    let source = args
+        .inputs
+        .iter()
+        .flat_map(|input| input.file_name())
+        .map(|name| name.to_string_lossy().to_string())
+        .collect::<Vec<String>>()
+        .join(", ");
+    let mut header = Header::new(Some(source));
+
+    // a single input is streamed, several inputs have to be merged in memory first
+    let elements = if let [input] = args.inputs.as_slice() {
+        read_elements(input)?
+    } else {
+        let mut merge = Merge::new();
+        // GeoJSON layers are read last, each counts its ids down in the first block of synthetic
+        // ids that is not used by the other inputs or the layers before it
+        let (layers, others): (Vec<&PathBuf>, Vec<&PathBuf>) = args
+            .inputs
+            .iter()
+            .partition(|input| InputFormat::from_path(input) == InputFormat::GeoJson);
+        for input in others.into_iter().chain(layers) {
+            let mut elements: Box<dyn ElementReader> =
+                if InputFormat::from_path(input) == InputFormat::GeoJson {
+                    let block = merge.free_synthetic_block();
+                    Box::new(MemoryReader::from(read_geojson(open_input(input)?, block)?))
+                } else {
+                    read_elements(input)?
+                };
+            for element in elements.by_ref() {
+                merge.add(element?);
+            }
+            if let Some(bounds) = elements.bounds() {
+                merge.add_bounds(bounds);
+            }
+        }
+        info!(
+            "merged {} inputs, {} duplicate elements dropped",
+            args.inputs.len(),
+            merge.duplicates()
+        );
+        Box::new(merge.into_reader())
+    };
+
+    // broken references are repaired or dropped so that the server never sees them
+    let mut elements = ValidatingReader::new(elements);
+    if mapped {
+        import_mapped(&args, &mut elements, &mut header)?;
+    } else {
+        import_binary(&args, compression, &mut elements, &mut header)?;
+    }
+    elements
+        .problems()
+        .iter()
+        .for_each(|problem| debug!("{}", problem));
+    summary(elements.problems())
+        .iter()
+        .for_each(|(kind, count)| warn!("repaired or dropped {} {}", count, kind));
+    info!(
+        "imported {} nodes, {} ways and {} relations from {}",
+        header.nodes,
+        header.ways,
+        header.relations,
+        header.source.as_deref().unwrap_or_default()
+    );
+    Ok(())
+}
+
+fn import_binary(
+    args: &ImportArgs,
+    compression: Compression,
+    elements: &mut dyn ElementReader,
+    header: &mut Header,
+) -> Result<()> {
+    // elements are written out as soon as they are read, the file is never fully in memory. The
+    // counts of the header are only known at the end, so the elements go to a temporary file
+    // that is appended to the header afterwards
+    let body_path = PathBuf::from(format!("{}.tmp", args.output.display()));
+    let mut writer = CborWriter::new(BufWriter::new(File::create(&body_path)?))?;
+    for element in &mut *elements {
+        let element = element?;
+        header.count(&element);
+        writer.write(&element)?;
+    }
+    header.bounds = elements.bounds().cloned();
+    let (mut body, table) = writer.finish(elements.bounds(), elements.generator())?;
+    body.flush()?;
+
+    let mut output = create_output(&args.output, compression)?;
+    write_header(&mut output, header, &table)?;
+    io::copy(&mut File::open(&body_path)?, &mut output)?;
+    output.finish()?;
+    fs::remove_file(&body_path)?;
+    Ok(())
+}
+
+fn import_mapped(
+    args: &ImportArgs,
+    elements: &mut dyn ElementReader,
+    header: &mut Header,
+) -> Result<()> {
+    let mut writer = MappedWriter::new();
+    for element in &mut *elements {
+        let element = element?;
+        header.count(&element);
+        writer.write(&element);
+    }
+    header.bounds = elements.bounds().cloned();
+    writer
+        .finish(create_output(&args.output, Compression::None)?, header)?
+        .finish()?;
+    Ok(())
+}
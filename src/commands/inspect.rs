@@ -0,0 +1,102 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use clap::Args;
+use osm_tiles::{
+    binary::{read_binary, Header, FORMAT_VERSION},
+    classes::registry,
+    compression::open_input,
+    input::{read_osm, InputFormat},
+    mapped::{self, MappedDataset},
+    utils::{relation_classes, way_classes},
+    Class, Result,
+};
+
+#[derive(Args)]
+pub struct InspectArgs {
+    /// dataset (osm.bin or a .map file) or OSM XML, PBF or JSON file
+    input: PathBuf,
+}
+
+fn print_header(header: &Header, format: &str) {
+    println!("format:    {}", format);
+    println!(
+        "source:    {}",
+        header.source.as_deref().unwrap_or("unknown")
+    );
+    println!("imported:  {}", header.imported_at);
+    println!("writer:    {}", header.written_by);
+}
+
+pub fn run(args: InspectArgs) -> Result<()> {
+    let osm = match InputFormat::from_path(&args.input) {
+        InputFormat::Cbor => {
+            let (header, osm) = read_binary(open_input(&args.input)?)?;
+            print_header(&header, &FORMAT_VERSION.to_string());
+            osm
+        }
+        InputFormat::Mapped => {
+            let dataset = MappedDataset::open(&args.input)?;
+            print_header(
+                dataset.header(),
+                &format!("{} (memory-mapped)", mapped::FORMAT_VERSION),
+            );
+            dataset.to_osm()
+        }
+        _ => read_osm(&args.input)?,
+    };
+
+    println!("nodes:     {}", osm.node.len());
+    println!("ways:      {}", osm.way.len());
+    println!("relations: {}", osm.relation.len());
+
+    if !osm.node.is_empty() {
+        let (min_lat, min_lon, max_lat, max_lon) = osm.node.iter().fold(
+            (f64::MAX, f64::MAX, f64::MIN, f64::MIN),
+            |(min_lat, min_lon, max_lat, max_lon), node| {
+                (
+                    min_lat.min(node.lat),
+                    min_lon.min(node.lon),
+                    max_lat.max(node.lat),
+                    max_lon.max(node.lon),
+                )
+            },
+        );
+        println!(
+            "bounds:    {:.7},{:.7} {:.7},{:.7}",
+            min_lat, min_lon, max_lat, max_lon
+        );
+    }
+
+    // elements of several classes are counted once for each of them
+    let count = |acc: &mut BTreeMap<&str, usize>, classes: Vec<Class>| {
+        if classes.is_empty() {
+            *acc.entry("unclassified").or_default() += 1;
+        }
+        for class in classes {
+            *acc.entry(registry()[class].name.as_str()).or_default() += 1;
+        }
+    };
+    let way_types = osm
+        .way
+        .iter()
+        .fold(BTreeMap::<&str, usize>::new(), |mut acc, way| {
+            count(&mut acc, way_classes(way));
+            acc
+        });
+    let relation_types =
+        osm.relation
+            .iter()
+            .fold(BTreeMap::<&str, usize>::new(), |mut acc, relation| {
+                count(&mut acc, relation_classes(relation));
+                acc
+            });
+    println!("ways by class:");
+    way_types
+        .iter()
+        .for_each(|(way_type, count)| println!("  {:<12} {}", way_type, count));
+    println!("relations by class:");
+    relation_types
+        .iter()
+        .for_each(|(relation_type, count)| println!("  {:<12} {}", relation_type, count));
+    Ok(())
+}
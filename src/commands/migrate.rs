@@ -0,0 +1,32 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use log::info;
+use osm_tiles::{
+    binary::{migrate, write_binary, FORMAT_VERSION},
+    compression::{create_output, open_input, Compression},
+    Result,
+};
+
+#[derive(Args)]
+pub struct MigrateArgs {
+    /// dataset written by an older osm-tiles
+    input: PathBuf,
+    /// converted dataset, defaults to overwriting the input
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+pub fn run(args: MigrateArgs) -> Result<()> {
+    let output_path = args.output.unwrap_or(args.input.clone());
+
+    let (header, osm) = migrate(open_input(&args.input)?)?;
+    let output = create_output(&output_path, Compression::from_path(&output_path))?;
+    write_binary(output, &header, &osm)?.finish()?;
+    info!(
+        "wrote {} with format version {}",
+        output_path.display(),
+        FORMAT_VERSION
+    );
+    Ok(())
+}
@@ -0,0 +1,8 @@
+pub mod apply;
+pub mod extract;
+pub mod import;
+pub mod inspect;
+pub mod migrate;
+pub mod render;
+pub mod serve;
+pub mod validate;
@@ -0,0 +1,161 @@
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+use cairo::{Context, ImageSurface};
+use clap::Args;
+use log::info;
+use osm_tiles::{
+    classes::registry,
+    input::read_osm,
+    utils::{convert_to_tile, relation_classes, relation_way_ids},
+    Error, NodeToTile, Osm, Relation, Result, Way, TILE_SIZE,
+};
+
+use super::serve::render_relation;
+
+#[derive(Args)]
+pub struct RenderArgs {
+    /// extract to render, as written by the extract command
+    #[arg(default_value = "temp.xml")]
+    input: PathBuf,
+    /// rendered PNG image
+    #[arg(short, long, default_value = "render-park.png")]
+    output: PathBuf,
+    #[arg(short, long, default_value_t = 16)]
+    zoom: u8,
+    /// space in pixels around the rendered relations
+    #[arg(long, default_value_t = 100f64)]
+    padding: f64,
+}
+
+pub async fn run(args: RenderArgs) -> Result<()> {
+    let zoom = args.zoom;
+    let osm: Osm = read_osm(&args.input)?;
+
+    let nodes_to_tile: NodeToTile =
+        osm.node
+            .iter()
+            .fold(HashMap::<u64, (f64, f64)>::new(), |mut acc, item| {
+                acc.insert(item.id, convert_to_tile(item.lat, item.lon));
+                acc
+            });
+
+    let dimension_in_pixels_for_zoom = f64::from(TILE_SIZE * (1 << zoom));
+
+    let mapped_nodes: NodeToTile = nodes_to_tile
+        .into_iter()
+        .map(|(id, (x, y))| {
+            (
+                id,
+                (
+                    x * dimension_in_pixels_for_zoom,
+                    y * dimension_in_pixels_for_zoom,
+                ),
+            )
+        })
+        .collect();
+
+    let id_to_ways = osm
+        .way
+        .iter()
+        .fold(HashMap::<u64, Arc<Way>>::new(), |mut acc, way| {
+            acc.insert(way.id, way.clone());
+            acc
+        });
+
+    let id_to_relations: HashMap<u64, Arc<Relation>> = osm
+        .relation
+        .iter()
+        .map(|relation| (relation.id, relation.clone()))
+        .collect();
+
+    let filtered_nodes: Vec<&(f64, f64)> = osm
+        .relation
+        .iter()
+        .flat_map(|relation| relation_way_ids(relation, &id_to_relations))
+        .flat_map(|way_id| id_to_ways.get(&way_id))
+        .flat_map(|way| way.nd.iter())
+        .map(|nd| nd.reference)
+        .flat_map(|node| mapped_nodes.get(&node))
+        .collect();
+
+    if filtered_nodes.is_empty() {
+        return Err(Error::NothingToRender(args.input.display().to_string()));
+    }
+    let mut min_x = filtered_nodes
+        .iter()
+        .map(|(x, _y)| *x)
+        .fold(f64::INFINITY, f64::min);
+    let mut min_y = filtered_nodes
+        .iter()
+        .map(|(_x, y)| *y)
+        .fold(f64::INFINITY, f64::min);
+
+    let mut max_x = filtered_nodes
+        .iter()
+        .map(|(x, _y)| *x)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let mut max_y = filtered_nodes
+        .iter()
+        .map(|(_x, y)| *y)
+        .fold(f64::NEG_INFINITY, f64::max);
+
+    min_x -= args.padding;
+    min_y -= args.padding;
+    max_x += args.padding;
+    max_y += args.padding;
+
+    info!("min_x {} min_y {}", min_x, min_y);
+
+    let width = max_x - min_x;
+    let height = max_y - min_y;
+    let surface = ImageSurface::create(cairo::Format::Rgb24, width as i32, height as i32)?;
+    let context = Context::new(&surface)?;
+    context.set_source_rgb(0.2, 0.2, 0.2);
+    context.paint()?;
+
+    context.set_line_width(1f64);
+    context.set_line_cap(cairo::LineCap::Round);
+    context.set_line_join(cairo::LineJoin::Round);
+
+    context.set_source_rgb(0.5, 0.5, 0.5);
+
+    info!("init nodes to order");
+    osm.relation.iter().for_each(|relation| {
+        relation_way_ids(relation, &id_to_relations)
+            .iter()
+            .flat_map(|way_id| id_to_ways.get(way_id))
+            .for_each(|way| {
+                way.nd
+                    .iter()
+                    .for_each(|node| info!("node {}-{}", way.id, node.reference));
+            })
+    });
+
+    // relations are drawn as on the tiles, holes are cut out of the filled areas
+    for relation in &osm.relation {
+        for class in relation_classes(relation) {
+            render_relation(
+                relation,
+                &registry()[class],
+                &context,
+                &id_to_ways,
+                &id_to_relations,
+                &mapped_nodes,
+                min_x,
+                min_y,
+                i32::from(args.zoom),
+            )?;
+        }
+    }
+
+    context.set_source_rgb(0.7, 0.7, 0.7);
+    context.line_to(width, 0 as f64);
+    context.line_to(0 as f64, 0 as f64);
+    context.line_to(0 as f64, height);
+    context.stroke()?;
+
+    let mut buffer = Vec::<u8>::new();
+    surface.write_to_png(&mut buffer)?;
+    tokio::fs::write(&args.output, &buffer).await?;
+    Ok(())
+}
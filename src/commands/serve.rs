@@ -0,0 +1,955 @@
+use axum::{
+    extract,
+    http::{header, Method, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Json, Router,
+};
+use cairo::{Context, ImageSurface};
+use clap::Args;
+use geo::Polygon;
+use log::{debug, error, info};
+use osm_tiles::{
+    binary::{read_binary, Header},
+    classes::{registry, FeatureClass, Style},
+    compression::open_input,
+    dataset::{Dataset, MemoryDataset},
+    input::InputFormat,
+    mapped::MappedDataset,
+    spatial::SpatialIndex,
+    utils::{
+        convert_to_tile, extract_loops_to_render, relation_way_ids, set_color,
+        set_context_for_line, stroke_line, tiles_for_pixel, trace_loop, way_ids_from_relations,
+    },
+    Class, Classes, ElementId, Error, MemberType, NodeToTile, Relation, RelationToTile, Result,
+    Symbol, Tagged, Way, WayToTile, TILE_SIZE,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::{sync::OnceCell, task::spawn_blocking};
+use tower_http::{
+    cors::{Any, CorsLayer},
+    services::ServeDir,
+};
+
+use super::extract::parse_bbox;
+
+struct Index {
+    relations_to_tile: RelationToTile,
+    ways_to_tile: WayToTile,
+    state: Arc<TileCacheState>,
+}
+
+fn add_to_tiles(
+    tiles: &mut HashMap<i32, HashMap<i32, HashSet<u64>>>,
+    pixel: (f64, f64),
+    zoom: u8,
+    id: u64,
+) {
+    tiles_for_pixel(pixel.0, pixel.1, zoom)
+        .into_iter()
+        .for_each(|tile| {
+            tiles
+                .entry(tile.0)
+                .or_default()
+                .entry(tile.1)
+                .or_default()
+                .insert(id);
+        });
+}
+
+fn build_index_for_zoom(state: Arc<TileCacheState>, zoom: u8) -> Index {
+    info!("build new cache for zoom {}", zoom);
+
+    let relations_to_tile = state.relations.iter().fold(
+        HashMap::<i32, HashMap<i32, HashSet<u64>>>::new(),
+        |mut acc, relation| {
+            relation_way_ids(relation, &state.id_to_relations)
+                .into_iter()
+                .flat_map(|way_id| state.dataset.way(way_id))
+                .for_each(|way| {
+                    way.nd
+                        .iter()
+                        .flat_map(|node| state.pixel(node.reference, zoom))
+                        .for_each(|pixel| add_to_tiles(&mut acc, pixel, zoom, relation.id));
+                });
+            acc
+        },
+    );
+
+    let ways_to_tile = state
+        .dataset
+        .way_nodes()
+        .filter(|(way_id, _)| !state.ways_from_relations.contains(way_id))
+        .fold(
+            HashMap::<i32, HashMap<i32, HashSet<u64>>>::new(),
+            |mut acc, (way_id, nodes)| {
+                nodes
+                    .into_iter()
+                    .flat_map(|node| state.pixel(node, zoom))
+                    .for_each(|pixel| add_to_tiles(&mut acc, pixel, zoom, way_id));
+                acc
+            },
+        );
+
+    Index {
+        relations_to_tile,
+        ways_to_tile,
+        state,
+    }
+}
+
+fn log_header(path: &Path, header: &Header) {
+    info!(
+        "loaded {} imported from {} at {}",
+        path.display(),
+        header.source.as_deref().unwrap_or("unknown source"),
+        header.imported_at
+    );
+}
+
+/// `.map` files are memory-mapped and queried in place, any other dataset is deserialized
+fn load_dataset(path: &Path) -> Result<Arc<dyn Dataset>> {
+    if InputFormat::from_path(path) == InputFormat::Mapped {
+        let dataset = MappedDataset::open(path)?;
+        log_header(path, dataset.header());
+        Ok(Arc::new(dataset))
+    } else {
+        let (header, osm) = read_binary(open_input(path)?)?;
+        log_header(path, &header);
+        Ok(Arc::new(MemoryDataset::from(&osm)))
+    }
+}
+
+/// highest zoom level served, pixel coordinates of higher levels do not fit in the tile index
+const MAX_ZOOM: i32 = 22;
+
+fn check_tile(z: i32, x: i32, y: i32) -> Result<()> {
+    let in_range = |coordinate: i32| (0..1 << z).contains(&coordinate);
+    if (0..=MAX_ZOOM).contains(&z) && in_range(x) && in_range(y) {
+        Ok(())
+    } else {
+        Err(Error::TileOutOfRange { z, x, y })
+    }
+}
+
+async fn render_tile_inner(z: i32, x: i32, y: i32, index: &Index) -> Result<Vec<u8>> {
+    check_tile(z, x, y)?;
+    let state = &index.state;
+    let ids_in_tile = |tiles: &HashMap<i32, HashMap<i32, HashSet<u64>>>| {
+        tiles
+            .get(&x)
+            .and_then(|inner| inner.get(&y))
+            .cloned()
+            .unwrap_or_default()
+    };
+
+    let filtered_relations = ids_in_tile(&index.relations_to_tile).iter().fold(
+        HashMap::<Class, Vec<Arc<Relation>>>::new(),
+        |mut acc, relation_id| {
+            let relation = state.id_to_relations.get(relation_id).unwrap();
+            for class in state.relation_to_classes.get(relation_id).unwrap() {
+                acc.entry(*class).or_default().push(relation.clone());
+            }
+            acc
+        },
+    );
+
+    // only the ways and nodes drawn on this tile are looked up in the dataset
+    let way_ids = ids_in_tile(&index.ways_to_tile);
+    let id_to_ways: HashMap<u64, Arc<Way>> = filtered_relations
+        .values()
+        .flatten()
+        .flat_map(|relation| relation_way_ids(relation, &state.id_to_relations))
+        .chain(way_ids.iter().cloned())
+        .flat_map(|way_id| state.dataset.way(way_id))
+        .map(|way| (way.id, way))
+        .collect();
+
+    let filtered_ways = way_ids
+        .iter()
+        .flat_map(|way_id| id_to_ways.get(way_id))
+        .fold(HashMap::<Class, Vec<Arc<Way>>>::new(), |mut acc, way| {
+            for class in state.classes.classify(way.as_ref()) {
+                acc.entry(class).or_default().push(way.clone());
+            }
+            acc
+        });
+
+    let mapped_nodes: NodeToTile = id_to_ways
+        .values()
+        .flat_map(|way| way.nd.iter())
+        .flat_map(|node| {
+            state
+                .pixel(node.reference, z as u8)
+                .map(|pixel| (node.reference, pixel))
+        })
+        .collect();
+
+    draw_to_memory(
+        z,
+        state.classes,
+        &mapped_nodes,
+        x as f64 * TILE_SIZE as f64,
+        y as f64 * TILE_SIZE as f64,
+        &filtered_relations,
+        &filtered_ways,
+        &id_to_ways,
+        &state.id_to_relations,
+    )
+}
+
+struct TileCacheState {
+    dataset: Arc<dyn Dataset>,
+    classes: &'static Classes,
+    relation_to_classes: HashMap<u64, Vec<Class>>,
+    id_to_relations: HashMap<u64, Arc<Relation>>,
+    relations: Vec<Arc<Relation>>,
+    /// ways drawn as part of a relation, they are not drawn on their own
+    ways_from_relations: HashSet<u64>,
+}
+
+impl TileCacheState {
+    /// pixel coordinates of the node at the zoom level
+    fn pixel(&self, node: u64, zoom: u8) -> Option<(f64, f64)> {
+        let dimension_in_pixels_for_zoom = f64::from(TILE_SIZE * (1 << zoom));
+        self.dataset.node(node).map(|(lat, lon)| {
+            let (x, y) = convert_to_tile(lat, lon);
+            (
+                x * dimension_in_pixels_for_zoom,
+                y * dimension_in_pixels_for_zoom,
+            )
+        })
+    }
+}
+
+struct TileCache {
+    /// index of each zoom level, built by the first tile requested at the level
+    cache: [OnceCell<Arc<Index>>; MAX_ZOOM as usize + 1],
+    state: Arc<TileCacheState>,
+}
+
+impl TileCache {
+    /// .
+    /// build index required for rendering. Relations are few, they are kept in memory with their
+    /// classes. The ways that are part of a relation are collected so that they are only drawn
+    /// with the relation. Nodes and ways are looked up in the dataset when the index for a zoom
+    /// level is built and when a tile is rendered
+    fn new_no_default(dataset: Arc<dyn Dataset>) -> Self {
+        let relations = dataset.relations();
+        let classes = registry();
+
+        let relation_to_classes = relations
+            .iter()
+            .map(|relation| (relation.id, classes.classify(relation.as_ref())))
+            .collect();
+
+        let id_to_relations =
+            relations
+                .iter()
+                .fold(HashMap::<u64, Arc<Relation>>::new(), |mut acc, relation| {
+                    acc.insert(relation.id, relation.clone());
+                    acc
+                });
+
+        let ways_from_relations =
+            way_ids_from_relations(relations.iter().map(Arc::as_ref), &id_to_relations);
+
+        TileCache {
+            cache: std::array::from_fn(|_| OnceCell::new()),
+            state: Arc::new(TileCacheState {
+                dataset,
+                classes,
+                relation_to_classes,
+                id_to_relations,
+                relations,
+                ways_from_relations,
+            }),
+        }
+    }
+
+    /// index of a zoom level checked with `check_tile`. It is built on the blocking thread pool,
+    /// concurrent requests for the level wait for the same build
+    async fn get_cache(&self, zoom: u8) -> Result<Arc<Index>> {
+        let index = self.cache[zoom as usize]
+            .get_or_try_init(|| {
+                let state = self.state.clone();
+                spawn_blocking(move || Arc::new(build_index_for_zoom(state, zoom)))
+            })
+            .await
+            .map_err(std::io::Error::from)?;
+        Ok(index.clone())
+    }
+}
+
+#[derive(Clone)]
+struct CacheDir(Arc<PathBuf>);
+
+/// failed tile request, tiles outside of the map are not found, anything else is logged as a
+/// server error
+struct TileError {
+    tile: (i32, i32, i32),
+    error: Error,
+}
+
+impl IntoResponse for TileError {
+    fn into_response(self) -> Response {
+        let (z, x, y) = self.tile;
+        let status = match self.error {
+            Error::TileOutOfRange { .. } => {
+                debug!("{}", self.error);
+                StatusCode::NOT_FOUND
+            }
+            _ => {
+                error!("failed to render tile {}/{}/{}: {}", z, x, y, self.error);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        };
+        (status, self.error.to_string()).into_response()
+    }
+}
+
+async fn render_tile_cache(
+    extract::Path((z, x, y)): extract::Path<(i32, i32, i32)>,
+    Extension(tile_cache): Extension<Arc<TileCache>>,
+    Extension(CacheDir(cache_dir)): Extension<CacheDir>,
+) -> std::result::Result<impl IntoResponse, TileError> {
+    let response = cached_tile(z, x, y, &tile_cache, &cache_dir)
+        .await
+        .map_err(|error| TileError {
+            tile: (z, x, y),
+            error,
+        })?;
+    Ok((
+        axum::response::AppendHeaders([
+            (header::CONTENT_TYPE, "image/png"),
+            (header::CACHE_CONTROL, "max-age=604800"),
+        ]),
+        response,
+    ))
+}
+
+async fn cached_tile(
+    z: i32,
+    x: i32,
+    y: i32,
+    tile_cache: &TileCache,
+    cache_dir: &Path,
+) -> Result<Vec<u8>> {
+    check_tile(z, x, y)?;
+    let new_path = cache_dir.join(format!("{}/{}/{}.png", z, x, y));
+    if new_path.is_file() {
+        return Ok(tokio::fs::read(&new_path).await?);
+    }
+    let index = tile_cache.get_cache(z as u8).await?;
+
+    let rendered_image = render_tile_inner(z, x, y, index.as_ref()).await?;
+
+    tokio::fs::create_dir_all(new_path.parent().unwrap()).await?;
+    tokio::fs::write(&new_path, &rendered_image).await?;
+    Ok(rendered_image)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn draw_to_memory(
+    z: i32,
+    classes: &Classes,
+    mapped_nodes: &HashMap<u64, (f64, f64)>,
+    min_x: f64,
+    min_y: f64,
+    relations_for_class: &HashMap<Class, Vec<Arc<Relation>>>,
+    ways_for_class: &HashMap<Class, Vec<Arc<Way>>>,
+    id_to_ways: &HashMap<u64, Arc<Way>>,
+    id_to_relations: &HashMap<u64, Arc<Relation>>,
+) -> Result<Vec<u8>> {
+    let surface = ImageSurface::create(cairo::Format::Rgb24, TILE_SIZE as i32, TILE_SIZE as i32)?;
+    let context = Context::new(&surface)?;
+    context.set_source_rgb(0.2, 0.2, 0.2);
+    context.paint()?;
+
+    context.set_line_width(1f64);
+    context.set_line_cap(cairo::LineCap::Round);
+    context.set_line_join(cairo::LineJoin::Round);
+
+    context.set_source_rgb(0.5, 0.5, 0.5);
+    context.set_line_width(1f64);
+
+    let mut casings_drawn = false;
+    for &class in classes.drawing_order() {
+        let feature_class = &classes[class];
+        // the casings of all the lines go below the first line with a casing, so that junctions
+        // are not outlined
+        if feature_class.style.casing.is_some() && !casings_drawn {
+            for &class in classes.drawing_order() {
+                for way in ways_for_class.get(&class).into_iter().flatten() {
+                    render_casing(
+                        way,
+                        &classes[class].style,
+                        &context,
+                        mapped_nodes,
+                        min_x,
+                        min_y,
+                        z,
+                    )?;
+                }
+            }
+            casings_drawn = true;
+        }
+
+        for way in ways_for_class.get(&class).into_iter().flatten() {
+            render_way(way, feature_class, &context, mapped_nodes, min_x, min_y, z)?;
+        }
+
+        for relation in relations_for_class.get(&class).into_iter().flatten() {
+            render_relation(
+                relation,
+                feature_class,
+                &context,
+                id_to_ways,
+                id_to_relations,
+                mapped_nodes,
+                min_x,
+                min_y,
+                z,
+            )?;
+        }
+    }
+
+    context.set_source_rgb(0.7, 0.7, 0.7);
+    context.line_to(TILE_SIZE as f64, 0 as f64);
+    context.line_to(0 as f64, 0 as f64);
+    context.line_to(0 as f64, TILE_SIZE as f64);
+    context.stroke()?;
+
+    let mut buffer = Vec::<u8>::new();
+    surface.write_to_png(&mut buffer)?;
+    Ok(buffer)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn render_relation(
+    relation: &Relation,
+    feature_class: &FeatureClass,
+    context: &Context,
+    id_to_ways: &HashMap<u64, Arc<Way>>,
+    id_to_relations: &HashMap<u64, Arc<Relation>>,
+    mapped_nodes: &HashMap<u64, (f64, f64)>,
+    min_x: f64,
+    min_y: f64,
+    z: i32,
+) -> Result<()> {
+    let style = &feature_class.style;
+    if z < style.min_zoom {
+        return Ok(());
+    }
+    let loops = extract_loops_to_render(relation, id_to_ways, id_to_relations);
+
+    // the closed rings of an area are filled as one path with the even-odd rule so that the
+    // inner rings are cut out as holes
+    if let Some(fill) = style.fill {
+        set_color(context, fill);
+        loops
+            .iter()
+            .filter(|ring| ring.closed)
+            .for_each(|ring| trace_loop(ring, context, mapped_nodes, min_x, min_y));
+        context.set_fill_rule(cairo::FillRule::EvenOdd);
+        context.fill()?;
+        context.set_fill_rule(cairo::FillRule::Winding);
+    }
+
+    // filled areas without a stroke colour only outline the rings that could not be closed
+    let outlined = loops
+        .iter()
+        .filter(|ring| style.stroke.is_some() || style.fill.is_none() || !ring.closed);
+    for ring in outlined {
+        trace_loop(ring, context, mapped_nodes, min_x, min_y);
+    }
+    stroke_line(style, z, context)?;
+
+    if let Some(key) = style.label.as_deref().filter(|_| z >= style.label_min_zoom) {
+        for ring in loops.iter().filter(|ring| !ring.inner) {
+            let label = ring
+                .way_id
+                .and_then(|way_id| id_to_ways.get(&way_id))
+                .and_then(|way| way.tag(key))
+                .or_else(|| relation.tag(key));
+            if let Some(label) = label {
+                render_label(
+                    label,
+                    &ring.memeber_loop,
+                    style,
+                    mapped_nodes,
+                    min_x,
+                    min_y,
+                    context,
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn render_way(
+    way: &Arc<Way>,
+    feature_class: &FeatureClass,
+    context: &Context,
+    mapped_nodes: &HashMap<u64, (f64, f64)>,
+    min_x: f64,
+    min_y: f64,
+    z: i32,
+) -> Result<()> {
+    let style = &feature_class.style;
+    if z < style.min_zoom {
+        return Ok(());
+    }
+    trace_way(way, context, mapped_nodes, min_x, min_y);
+
+    match (style.fill, style.stroke) {
+        (Some(fill), Some(_)) => {
+            set_color(context, fill);
+            context.fill_preserve()?;
+            stroke_line(style, z, context)?;
+        }
+        (Some(fill), None) => {
+            set_color(context, fill);
+            context.fill()?;
+        }
+        (None, _) => stroke_line(style, z, context)?,
+    }
+
+    if let Some(key) = style.label.as_deref().filter(|_| z >= style.label_min_zoom) {
+        if let Some(label) = way.tag(key) {
+            let nodes: Vec<u64> = way.nd.iter().map(|nd| nd.reference).collect();
+            render_label(label, &nodes, style, mapped_nodes, min_x, min_y, context)?;
+        }
+    }
+    Ok(())
+}
+
+fn trace_way(
+    way: &Way,
+    context: &Context,
+    mapped_nodes: &HashMap<u64, (f64, f64)>,
+    min_x: f64,
+    min_y: f64,
+) {
+    way.nd
+        .iter()
+        .flat_map(|nd| mapped_nodes.get(&nd.reference))
+        .map(|(x, y)| {
+            let x = x - min_x;
+            let y = y - min_y;
+            (x, y)
+        })
+        .for_each(|(x, y)| {
+            context.line_to(x, y);
+        });
+}
+
+/// draws the casing of a line, lines are left out below their minimum zoom
+fn render_casing(
+    way: &Way,
+    style: &Style,
+    context: &Context,
+    mapped_nodes: &HashMap<u64, (f64, f64)>,
+    min_x: f64,
+    min_y: f64,
+    z: i32,
+) -> Result<()> {
+    if z < style.min_zoom || style.casing.is_none() {
+        return Ok(());
+    }
+    set_context_for_line(style, z, true, context);
+    trace_way(way, context, mapped_nodes, min_x, min_y);
+    context.stroke()?;
+    Ok(())
+}
+
+/// writes the label inside of the outline, in the stroke colour of the class
+fn render_label(
+    label: Symbol,
+    ordered_nodes: &[u64],
+    style: &Style,
+    mapped_nodes: &HashMap<u64, (f64, f64)>,
+    min_x: f64,
+    min_y: f64,
+    context: &Context,
+) -> Result<()> {
+    let points: Vec<(f64, f64)> = ordered_nodes
+        .iter()
+        .flat_map(|node| mapped_nodes.get(node))
+        .cloned()
+        .collect();
+    let poly = Polygon::new(points.into(), vec![]);
+    // no label for outlines polylabel can not place one in
+    if let Ok(label_position) = polylabel::polylabel(&poly, &0.1) {
+        if let Some(color) = style.stroke.or(style.fill) {
+            set_color(context, color);
+        }
+        context.move_to(label_position.x() - min_x, label_position.y() - min_y);
+        context.show_text(&label)?;
+        context.new_path();
+    }
+    Ok(())
+}
+
+/// spatial index of the dataset for the feature queries, built by the first query so that it
+/// does not delay the start of the server
+#[derive(Clone)]
+struct FeatureIndex {
+    dataset: Arc<dyn Dataset>,
+    index: Arc<OnceCell<SpatialIndex>>,
+}
+
+impl FeatureIndex {
+    /// the index is built on the blocking thread pool, concurrent queries wait for the same build
+    async fn get(&self) -> Result<&SpatialIndex> {
+        let dataset = self.dataset.clone();
+        let index = self
+            .index
+            .get_or_try_init(|| {
+                spawn_blocking(move || {
+                    let index = SpatialIndex::new(dataset.as_ref());
+                    info!("indexed {} features", index.len());
+                    index
+                })
+            })
+            .await
+            .map_err(std::io::Error::from)?;
+        Ok(index)
+    }
+}
+
+/// features in `bbox`, given as minlat,minlon,maxlat,maxlon, or around `lat` and `lon`
+#[derive(Deserialize, Default)]
+struct FeatureQuery {
+    bbox: Option<String>,
+    lat: Option<f64>,
+    lon: Option<f64>,
+    /// metres around the location, only the nearest feature is returned without it
+    radius: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct Feature {
+    #[serde(rename = "type")]
+    element_type: MemberType,
+    id: u64,
+    /// metres from the location of the query
+    #[serde(skip_serializing_if = "Option::is_none")]
+    distance: Option<f64>,
+}
+
+impl Feature {
+    fn new(element: ElementId, distance: Option<f64>) -> Self {
+        let (element_type, id) = match element {
+            ElementId::Node(id) => (MemberType::Node, id),
+            ElementId::Way(id) => (MemberType::Way, id),
+            ElementId::Relation(id) => (MemberType::Relation, id),
+        };
+        Feature {
+            element_type,
+            id,
+            distance,
+        }
+    }
+}
+
+/// rejects coordinates that are not on the globe, including infinite and NaN values
+fn check_coordinates(lat: f64, lon: f64) -> std::result::Result<(), String> {
+    if !(-90.0..=90.0).contains(&lat) {
+        Err(format!("lat {} is not between -90 and 90", lat))
+    } else if !(-180.0..=180.0).contains(&lon) {
+        Err(format!("lon {} is not between -180 and 180", lon))
+    } else {
+        Ok(())
+    }
+}
+
+fn find_features(
+    index: &SpatialIndex,
+    query: &FeatureQuery,
+) -> std::result::Result<Vec<Feature>, String> {
+    match query {
+        FeatureQuery {
+            bbox: Some(bbox), ..
+        } => {
+            let bounds = parse_bbox(bbox)?;
+            check_coordinates(bounds.minlat, bounds.minlon)?;
+            check_coordinates(bounds.maxlat, bounds.maxlon)?;
+            Ok(index
+                .features_in_bbox(&bounds)
+                .into_iter()
+                .map(|element| Feature::new(element, None))
+                .collect())
+        }
+        FeatureQuery {
+            lat: Some(lat),
+            lon: Some(lon),
+            radius,
+            ..
+        } => {
+            check_coordinates(*lat, *lon)?;
+            let features = match radius {
+                Some(radius) if !radius.is_finite() || *radius < 0.0 => {
+                    return Err(format!("radius {} is not a distance in metres", radius));
+                }
+                Some(radius) => index.features_within_radius(*lat, *lon, *radius),
+                None => index.nearest_feature(*lat, *lon).into_iter().collect(),
+            };
+            Ok(features
+                .into_iter()
+                .map(|(element, distance)| Feature::new(element, Some(distance)))
+                .collect())
+        }
+        _ => Err("expected a bbox or a lat and lon".to_string()),
+    }
+}
+
+async fn features(
+    extract::Query(query): extract::Query<FeatureQuery>,
+    Extension(feature_index): Extension<FeatureIndex>,
+) -> std::result::Result<Json<Vec<Feature>>, (StatusCode, String)> {
+    let index = feature_index.get().await.map_err(|error| {
+        error!("failed to index the features: {}", error);
+        (StatusCode::INTERNAL_SERVER_ERROR, error.to_string())
+    })?;
+    find_features(index, &query)
+        .map(Json)
+        .map_err(|error| (StatusCode::BAD_REQUEST, error))
+}
+
+#[derive(Args)]
+pub struct ServeArgs {
+    /// dataset written by the import command
+    #[arg(long, default_value = "osm.bin")]
+    data: PathBuf,
+    /// address the server listens on
+    #[arg(long, default_value = "0.0.0.0:4000")]
+    bind: String,
+    /// directory with the static web client served on /
+    #[arg(long, default_value = "../solid-leaflet-reprex/dist")]
+    static_dir: PathBuf,
+    /// directory where rendered tiles are cached
+    #[arg(long, default_value = "./cached")]
+    cache_dir: PathBuf,
+}
+
+pub async fn run(args: ServeArgs) -> Result<()> {
+    // let buffer = BufReader::new(File::open("temp.xml").unwrap());
+    // let osm: Osm = quick_xml::de::from_reader(buffer).unwrap();
+
+    let dataset = load_dataset(&args.data)?;
+
+    let cors = CorsLayer::new()
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
+        .allow_headers(Any)
+        .allow_origin(Any);
+
+    let app = Router::new()
+        .nest_service("/", ServeDir::new(&args.static_dir))
+        .route("/map/:z/:x/:y", get(render_tile_cache))
+        .route("/features", get(features))
+        .layer(Extension(FeatureIndex {
+            dataset: dataset.clone(),
+            index: Arc::new(OnceCell::new()),
+        }))
+        .layer(Extension(Arc::new(TileCache::new_no_default(dataset))))
+        .layer(Extension(CacheDir(Arc::new(args.cache_dir))))
+        .layer(cors);
+
+    info!("listening on {}", args.bind);
+    axum::serve(tokio::net::TcpListener::bind(&args.bind).await?, app).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::{HashMap, HashSet},
+        sync::Arc,
+    };
+
+    use osm_tiles::{
+        dataset::{Dataset, MemoryDataset},
+        spatial::SpatialIndex,
+        stream::XmlReader,
+        ElementId, Error, MemberType, Osm,
+    };
+
+    use super::{check_tile, find_features, render_tile_inner, Feature, FeatureQuery, TileCache};
+
+    /// the park, the road and the lake are in tile 13/4753/2881, the cafe and the track to the
+    /// north of it. Way 13 of the lake is not in the dataset
+    const OSM_XML: &str = r#"<osm version="0.6">
+  <node id="1" lat="46.99" lon="28.88"/>
+  <node id="2" lat="46.99" lon="28.89"/>
+  <node id="3" lat="47.0" lon="28.89"/>
+  <node id="4" lat="47.0" lon="28.88"/>
+  <node id="5" lat="46.985" lon="28.9"/>
+  <node id="6" lat="47.005" lon="28.91"/>
+  <node id="7" lat="46.99" lon="28.9"/>
+  <node id="8" lat="46.99" lon="28.91"/>
+  <node id="9" lat="47.0" lon="28.905"/>
+  <node id="30" lat="47.5" lon="28.9"><tag k="amenity" v="cafe"/></node>
+  <node id="31" lat="47.6" lon="28.9"/>
+  <way id="10"><nd ref="1"/><nd ref="2"/><nd ref="3"/><nd ref="4"/><nd ref="1"/><tag k="leisure" v="park"/></way>
+  <way id="11"><nd ref="5"/><nd ref="6"/><tag k="highway" v="primary"/></way>
+  <way id="12"><nd ref="7"/><nd ref="8"/><nd ref="9"/><nd ref="7"/></way>
+  <way id="14"><nd ref="30"/><nd ref="31"/></way>
+  <relation id="20">
+    <member type="way" ref="12" role="outer"/>
+    <member type="way" ref="13" role="inner"/>
+    <tag k="type" v="multipolygon"/>
+    <tag k="natural" v="water"/>
+  </relation>
+</osm>"#;
+
+    fn dataset() -> Arc<dyn Dataset> {
+        let mut osm = Osm::default();
+        XmlReader::new(OSM_XML.as_bytes()).for_each(|element| osm.push(element.unwrap()));
+        Arc::new(MemoryDataset::from(&osm))
+    }
+
+    fn ids_in_tile(tiles: &HashMap<i32, HashMap<i32, HashSet<u64>>>) -> HashSet<u64> {
+        tiles
+            .get(&4753)
+            .and_then(|column| column.get(&2881))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn element(feature: &Feature) -> ElementId {
+        match feature.element_type {
+            MemberType::Node => ElementId::Node(feature.id),
+            MemberType::Way => ElementId::Way(feature.id),
+            MemberType::Relation => ElementId::Relation(feature.id),
+        }
+    }
+
+    #[tokio::test]
+    async fn render_tile_test() {
+        let tile_cache = TileCache::new_no_default(dataset());
+        let index = tile_cache.get_cache(13).await.unwrap();
+        // way 12 is only drawn with the lake
+        assert_eq!(ids_in_tile(&index.ways_to_tile), HashSet::from([10, 11]));
+        assert_eq!(ids_in_tile(&index.relations_to_tile), HashSet::from([20]));
+
+        let data = render_tile_inner(13, 4753, 2881, &index).await.unwrap();
+        assert!(data.starts_with(b"\x89PNG"));
+    }
+
+    #[test]
+    fn reject_tiles_outside_of_the_map() {
+        assert!(check_tile(13, 4753, 2881).is_ok());
+        for (z, x, y) in [(13, 8192, 0), (2, -1, 0), (-1, 0, 0), (23, 0, 0)] {
+            assert!(matches!(
+                check_tile(z, x, y),
+                Err(Error::TileOutOfRange { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn answer_feature_queries() {
+        let index = SpatialIndex::new(dataset().as_ref());
+        let ids = |query: &FeatureQuery| {
+            let mut ids: Vec<ElementId> = find_features(&index, query)
+                .unwrap()
+                .iter()
+                .map(element)
+                .collect();
+            ids.sort();
+            ids
+        };
+
+        let everywhere = FeatureQuery {
+            bbox: Some("-85,-180,85,180".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            ids(&everywhere),
+            vec![
+                ElementId::Node(30),
+                ElementId::Way(10),
+                ElementId::Way(11),
+                ElementId::Relation(20)
+            ]
+        );
+
+        // inside of the park
+        let nearest = FeatureQuery {
+            lat: Some(46.995),
+            lon: Some(28.885),
+            ..Default::default()
+        };
+        let features = find_features(&index, &nearest).unwrap();
+        assert_eq!(features.len(), 1);
+        assert_eq!(element(&features[0]), ElementId::Way(10));
+        assert_eq!(features[0].distance, Some(0.0));
+
+        // 0.1° of latitude south of the cafe
+        let around = FeatureQuery {
+            lat: Some(47.4),
+            lon: Some(28.9),
+            radius: Some(12_000.0),
+            ..Default::default()
+        };
+        assert_eq!(ids(&around), vec![ElementId::Node(30)]);
+
+        assert!(find_features(&index, &FeatureQuery::default()).is_err());
+    }
+
+    #[test]
+    fn reject_invalid_feature_queries() {
+        let index = SpatialIndex::new(dataset().as_ref());
+        let error = |lat: f64, lon: f64, radius: Option<f64>| {
+            find_features(
+                &index,
+                &FeatureQuery {
+                    lat: Some(lat),
+                    lon: Some(lon),
+                    radius,
+                    ..Default::default()
+                },
+            )
+            .err()
+        };
+
+        assert_eq!(error(90.0, 180.0, Some(0.0)), None);
+        assert_eq!(
+            error(90.5, 28.9, None).unwrap(),
+            "lat 90.5 is not between -90 and 90"
+        );
+        assert_eq!(
+            error(47.0, -180.5, None).unwrap(),
+            "lon -180.5 is not between -180 and 180"
+        );
+        assert!(error(f64::NAN, 28.9, None).is_some());
+        assert!(error(47.0, f64::INFINITY, None).is_some());
+        assert_eq!(
+            error(47.0, 28.9, Some(-1.0)).unwrap(),
+            "radius -1 is not a distance in metres"
+        );
+        assert!(error(47.0, 28.9, Some(f64::INFINITY)).is_some());
+        assert!(error(47.0, 28.9, Some(f64::NAN)).is_some());
+
+        let bbox = |bbox: &str| {
+            find_features(
+                &index,
+                &FeatureQuery {
+                    bbox: Some(bbox.to_string()),
+                    ..Default::default()
+                },
+            )
+            .is_err()
+        };
+        assert!(!bbox("-90,-180,90,180"));
+        assert!(bbox("-91,-180,90,180"));
+        assert!(bbox("-90,-180,90,inf"));
+    }
+}
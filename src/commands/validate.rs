@@ -0,0 +1,37 @@
+use std::{path::PathBuf, process};
+
+use clap::Args;
+use osm_tiles::{
+    input::read_elements,
+    validate::{summary, ValidatingReader},
+    Result,
+};
+
+#[derive(Args)]
+pub struct ValidateArgs {
+    /// dataset or OSM XML, PBF or JSON file
+    input: PathBuf,
+}
+
+/// exits with 1 when problems are found
+pub fn run(args: ValidateArgs) -> Result<()> {
+    let mut elements = ValidatingReader::new(read_elements(&args.input)?);
+    for element in elements.by_ref() {
+        element?;
+    }
+
+    elements
+        .problems()
+        .iter()
+        .for_each(|problem| println!("{}", problem));
+    let summary = summary(elements.problems());
+    if summary.is_empty() {
+        println!("no problems found");
+        return Ok(());
+    }
+    println!("problems:");
+    summary
+        .iter()
+        .for_each(|(kind, count)| println!("  {:<26} {}", kind, count));
+    process::exit(1);
+}
@@ -0,0 +1,129 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use bzip2::{read::MultiBzDecoder, write::BzEncoder};
+use flate2::{read::MultiGzDecoder, write::GzEncoder};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const BZIP2_MAGIC: &[u8] = b"BZh";
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Compression {
+    None,
+    Gzip,
+    Bzip2,
+    Zstd,
+}
+
+impl Compression {
+    /// compression matching the extension of the file, `.gz`, `.bz2` or `.zst`
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("gz") => Compression::Gzip,
+            Some("bz2") => Compression::Bzip2,
+            Some("zst") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+
+    /// compression detected from the first bytes of the stream
+    pub fn from_magic(header: &[u8]) -> Self {
+        if header.starts_with(GZIP_MAGIC) {
+            Compression::Gzip
+        } else if header.starts_with(BZIP2_MAGIC) {
+            Compression::Bzip2
+        } else if header.starts_with(ZSTD_MAGIC) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+}
+
+/// wraps the reader in a decoder when the stream starts with a gzip, bzip2 or zstd header, plain
+/// streams are returned as they are
+pub fn decompress<'a, R: BufRead + 'a>(mut reader: R) -> io::Result<Box<dyn BufRead + 'a>> {
+    let compression = Compression::from_magic(reader.fill_buf()?);
+    Ok(match compression {
+        Compression::None => Box::new(reader),
+        Compression::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+        Compression::Bzip2 => Box::new(BufReader::new(MultiBzDecoder::new(reader))),
+        Compression::Zstd => Box::new(BufReader::new(zstd::Decoder::with_buffer(reader)?)),
+    })
+}
+
+/// error of a file operation with the path of the file, io errors do not carry it
+pub(crate) fn path_error(path: &Path, error: io::Error) -> io::Error {
+    io::Error::new(error.kind(), format!("{}: {}", path.display(), error))
+}
+
+/// opens an input file, transparently decompressing it
+pub fn open_input(path: impl AsRef<Path>) -> io::Result<Box<dyn BufRead>> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|error| path_error(path, error))?;
+    decompress(BufReader::new(file))
+}
+
+/// output file compressed with the requested algorithm, `finish` has to be called to write the
+/// trailer of the compressed stream
+pub enum CompressedWriter {
+    None(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    Bzip2(BzEncoder<BufWriter<File>>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+impl CompressedWriter {
+    pub fn finish(self) -> io::Result<()> {
+        let mut writer = match self {
+            CompressedWriter::None(writer) => writer,
+            CompressedWriter::Gzip(encoder) => encoder.finish()?,
+            CompressedWriter::Bzip2(encoder) => encoder.finish()?,
+            CompressedWriter::Zstd(encoder) => encoder.finish()?,
+        };
+        writer.flush()
+    }
+}
+
+impl Write for CompressedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            CompressedWriter::None(writer) => writer.write(buf),
+            CompressedWriter::Gzip(encoder) => encoder.write(buf),
+            CompressedWriter::Bzip2(encoder) => encoder.write(buf),
+            CompressedWriter::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            CompressedWriter::None(writer) => writer.flush(),
+            CompressedWriter::Gzip(encoder) => encoder.flush(),
+            CompressedWriter::Bzip2(encoder) => encoder.flush(),
+            CompressedWriter::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// creates an output file, compressed according to `compression`
+pub fn create_output(
+    path: impl AsRef<Path>,
+    compression: Compression,
+) -> io::Result<CompressedWriter> {
+    let path = path.as_ref();
+    let writer = BufWriter::new(File::create(path).map_err(|error| path_error(path, error))?);
+    Ok(match compression {
+        Compression::None => CompressedWriter::None(writer),
+        Compression::Gzip => {
+            CompressedWriter::Gzip(GzEncoder::new(writer, flate2::Compression::default()))
+        }
+        Compression::Bzip2 => {
+            CompressedWriter::Bzip2(BzEncoder::new(writer, bzip2::Compression::default()))
+        }
+        Compression::Zstd => CompressedWriter::Zstd(zstd::Encoder::new(writer, 0)?),
+    })
+}
@@ -0,0 +1,98 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{Element, Osm, Relation, Tagged, Way};
+
+/// lookups the tile server renders from, implemented by the in-memory dataset and by the
+/// memory-mapped one
+pub trait Dataset: Send + Sync {
+    /// latitude and longitude of the node
+    fn node(&self, id: u64) -> Option<(f64, f64)>;
+    /// ids and positions of the nodes that have tags, the others are only parts of ways
+    fn tagged_nodes(&self) -> Box<dyn Iterator<Item = (u64, (f64, f64))> + '_>;
+    fn way(&self, id: u64) -> Option<Arc<Way>>;
+    fn ways(&self) -> Box<dyn Iterator<Item = Arc<Way>> + '_>;
+    /// ids of all the ways with their node references
+    fn way_nodes(&self) -> Box<dyn Iterator<Item = (u64, Vec<u64>)> + '_>;
+    fn relations(&self) -> Vec<Arc<Relation>>;
+}
+
+/// dataset deserialized from osm.bin
+#[derive(Default)]
+pub struct MemoryDataset {
+    nodes: HashMap<u64, (f64, f64)>,
+    tagged_nodes: Vec<u64>,
+    ways: HashMap<u64, Arc<Way>>,
+    relations: Vec<Arc<Relation>>,
+}
+
+impl From<&Osm> for MemoryDataset {
+    fn from(osm: &Osm) -> Self {
+        MemoryDataset {
+            nodes: osm
+                .node
+                .iter()
+                .map(|node| (node.id, (node.lat, node.lon)))
+                .collect(),
+            tagged_nodes: osm
+                .node
+                .iter()
+                .filter(|node| !node.tags().is_empty())
+                .map(|node| node.id)
+                .collect(),
+            ways: osm.way.iter().map(|way| (way.id, way.clone())).collect(),
+            relations: osm.relation.clone(),
+        }
+    }
+}
+
+impl MemoryDataset {
+    /// adds an element to a dataset built while the elements are streamed
+    pub fn insert(&mut self, element: &Element) {
+        match element {
+            Element::Node(node) => {
+                self.nodes.insert(node.id, (node.lat, node.lon));
+                if !node.tags().is_empty() {
+                    self.tagged_nodes.push(node.id);
+                }
+            }
+            Element::Way(way) => {
+                self.ways.insert(way.id, Arc::new(way.clone()));
+            }
+            Element::Relation(relation) => self.relations.push(Arc::new(relation.clone())),
+        }
+    }
+}
+
+impl Dataset for MemoryDataset {
+    fn node(&self, id: u64) -> Option<(f64, f64)> {
+        self.nodes.get(&id).cloned()
+    }
+
+    fn tagged_nodes(&self) -> Box<dyn Iterator<Item = (u64, (f64, f64))> + '_> {
+        Box::new(
+            self.tagged_nodes
+                .iter()
+                .flat_map(|&id| self.nodes.get(&id).map(|&position| (id, position))),
+        )
+    }
+
+    fn way(&self, id: u64) -> Option<Arc<Way>> {
+        self.ways.get(&id).cloned()
+    }
+
+    fn ways(&self) -> Box<dyn Iterator<Item = Arc<Way>> + '_> {
+        Box::new(self.ways.values().cloned())
+    }
+
+    fn way_nodes(&self) -> Box<dyn Iterator<Item = (u64, Vec<u64>)> + '_> {
+        Box::new(
+            self.ways
+                .values()
+                .map(|way| (way.id, way.nd.iter().map(|nd| nd.reference).collect())),
+        )
+    }
+
+    fn relations(&self) -> Vec<Arc<Relation>> {
+        self.relations.clone()
+    }
+}
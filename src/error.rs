@@ -0,0 +1,48 @@
+use std::io;
+
+use thiserror::Error;
+
+use crate::ElementId;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("the dataset was written by an older osm-tiles; convert it with `osm-tiles migrate` or import the data again")]
+    LegacyDataset,
+    #[error("the dataset has format version {found} but this osm-tiles reads version {expected}; import the data again")]
+    UnsupportedVersion { found: u32, expected: u32 },
+    #[error("corrupt dataset: {0}")]
+    Corrupt(String),
+    #[error("{element} references missing {missing}")]
+    MissingReference {
+        element: ElementId,
+        missing: ElementId,
+    },
+    #[error("{element} has rings that are not closed")]
+    UnclosedRing { element: ElementId },
+    #[error("{element} has no area")]
+    NoArea { element: ElementId },
+    #[error("invalid feature classes: {0}")]
+    InvalidClasses(String),
+    #[error("invalid GeoJSON: {0}")]
+    InvalidGeoJson(String),
+    #[error("no synthetic ids are left for the elements of GeoJSON layer {block}")]
+    SyntheticIdsExhausted { block: u64 },
+    #[error("invalid filter at column {column}: {message}")]
+    InvalidFilter { column: usize, message: String },
+    #[error("{element} is not in {input}")]
+    MissingElement { element: ElementId, input: String },
+    #[error("memory-mapped datasets can not be compressed")]
+    CompressedMappedDataset,
+    #[error("{0} has no relations to render")]
+    NothingToRender(String),
+    #[error("tile {z}/{x}/{y} does not exist")]
+    TileOutOfRange { z: i32, x: i32, y: i32 },
+    #[error("rendering failed: {0}")]
+    Cairo(#[from] cairo::Error),
+    #[error("PNG encoding failed: {0}")]
+    Png(#[from] cairo::IoError),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,497 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use geo::{BoundingRect, Intersects, MultiPolygon, Point, Rect};
+
+use crate::{
+    dataset::Dataset, geometry::relation_to_multipolygon, utils::nested_relation_ids, Bounds,
+    ElementId, Error, Filter, MemberType, Node, Osm, Relation, Result, Way,
+};
+
+/// area an extract is limited to
+#[derive(Clone, Debug)]
+pub enum Region {
+    Bounds(Bounds),
+    /// polygon of a boundary relation with its bounding box, most nodes are outside of the box
+    /// and are not tested against the polygon
+    Polygon(MultiPolygon, Rect),
+}
+
+impl Region {
+    /// region of a boundary relation, its member ways and their nodes are looked up in the
+    /// dataset
+    pub fn boundary<D: Dataset + ?Sized>(relation: &Relation, dataset: &D) -> Result<Self> {
+        let polygon = relation_to_multipolygon(relation, dataset)?;
+        let rect = polygon.bounding_rect().ok_or(Error::NoArea {
+            element: ElementId::Relation(relation.id),
+        })?;
+        Ok(Region::Polygon(polygon, rect))
+    }
+
+    /// whether the location is inside of the region or on its border
+    pub fn contains(&self, lat: f64, lon: f64) -> bool {
+        match self {
+            Region::Bounds(bounds) => {
+                (bounds.minlat..=bounds.maxlat).contains(&lat)
+                    && (bounds.minlon..=bounds.maxlon).contains(&lon)
+            }
+            Region::Polygon(polygon, rect) => {
+                let point = Point::new(lon, lat);
+                rect.intersects(&point) && polygon.intersects(&point)
+            }
+        }
+    }
+
+    /// bounding box of the region, the bounds of the extract
+    pub fn bounds(&self) -> Bounds {
+        match self {
+            Region::Bounds(bounds) => bounds.clone(),
+            Region::Polygon(_, rect) => Bounds {
+                minlat: rect.min().y,
+                minlon: rect.min().x,
+                maxlat: rect.max().y,
+                maxlon: rect.max().x,
+            },
+        }
+    }
+}
+
+/// how elements crossing the border of the region are extracted
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Strategy {
+    /// ways keep all their nodes and relations all their members, also those outside of the
+    /// region, so that their geometries are complete
+    Complete,
+    /// nothing outside of the region is kept, ways lose their nodes outside of it and relations
+    /// their members. Ways with less than two nodes inside are dropped
+    Clipped,
+}
+
+/// ids of the elements inside of a region
+#[derive(Default, Debug)]
+struct Inside {
+    nodes: HashSet<u64>,
+    ways: HashSet<u64>,
+    relations: HashSet<u64>,
+}
+
+/// ids of the elements of an extract, the elements matching a filter and everything they
+/// reference so that the extract is complete. Relations are selected first, then ways and
+/// nodes, each selection adds the referenced elements of the types selected after it.
+///
+/// When the extract is limited to a region, nodes and ways are located first: nodes inside of
+/// the region, ways with nodes inside and relations with members inside are in the region, and
+/// only elements in the region match the filter
+#[derive(Default, Debug)]
+pub struct Selection {
+    pub nodes: HashSet<u64>,
+    pub ways: HashSet<u64>,
+    pub relations: HashSet<u64>,
+    region: Option<(Region, Strategy, Inside)>,
+}
+
+impl Selection {
+    /// selection limited to the region
+    pub fn within(region: Region, strategy: Strategy) -> Self {
+        Selection {
+            region: Some((region, strategy, Inside::default())),
+            ..Default::default()
+        }
+    }
+
+    /// whether nodes, ways and relations have to be located before they are selected
+    pub fn has_region(&self) -> bool {
+        self.region.is_some()
+    }
+
+    /// records whether the node is inside of the region
+    pub fn locate_node(&mut self, node: &Node) {
+        if let Some((region, _, inside)) = &mut self.region {
+            if region.contains(node.lat, node.lon) {
+                inside.nodes.insert(node.id);
+            }
+        }
+    }
+
+    /// records whether the way is in the region, after all of its nodes were located. Clipped
+    /// ways need two distinct nodes inside to keep a line
+    pub fn locate_way(&mut self, way: &Way) {
+        if let Some((_, strategy, inside)) = &mut self.region {
+            let mut nodes = way
+                .nd
+                .iter()
+                .map(|nd| nd.reference)
+                .filter(|id| inside.nodes.contains(id));
+            // the first node of a closed way is also its last
+            let in_region = match (strategy, nodes.next()) {
+                (_, None) => false,
+                (Strategy::Complete, Some(_)) => true,
+                (Strategy::Clipped, Some(first)) => nodes.any(|id| id != first),
+            };
+            if in_region {
+                inside.ways.insert(way.id);
+            }
+        }
+    }
+
+    /// records the relations with members in the region, after the nodes and ways were located
+    pub fn locate_relations(&mut self, id_to_relations: &HashMap<u64, Arc<Relation>>) {
+        let Some((_, _, inside)) = &mut self.region else {
+            return;
+        };
+        // relations with only relation members are inside once one of those is
+        loop {
+            let located = inside.relations.len();
+            for relation in id_to_relations.values() {
+                let in_region = !inside.relations.contains(&relation.id)
+                    && relation.member.iter().any(|member| {
+                        let ids = match member.member_type {
+                            MemberType::Node => &inside.nodes,
+                            MemberType::Way => &inside.ways,
+                            MemberType::Relation => &inside.relations,
+                        };
+                        ids.contains(&member.member_ref)
+                    });
+                if in_region {
+                    inside.relations.insert(relation.id);
+                }
+            }
+            if inside.relations.len() == located {
+                break;
+            }
+        }
+    }
+
+    /// whether the element is in the region, all elements are when there is no region
+    fn in_region(&self, element: ElementId) -> bool {
+        match &self.region {
+            None => true,
+            Some((_, _, inside)) => match element {
+                ElementId::Node(id) => inside.nodes.contains(&id),
+                ElementId::Way(id) => inside.ways.contains(&id),
+                ElementId::Relation(id) => inside.relations.contains(&id),
+            },
+        }
+    }
+
+    /// whether elements outside of the region are dropped, the references to them have to be
+    /// clipped from the selected ways and relations
+    pub fn clips(&self) -> bool {
+        matches!(self.region, Some((_, Strategy::Clipped, _)))
+    }
+
+    /// selects the matching relations with the relations nested in them, and their member ways
+    /// and nodes
+    pub fn select_relations(
+        &mut self,
+        filter: &Filter,
+        id_to_relations: &HashMap<u64, Arc<Relation>>,
+    ) {
+        let matching: Vec<&Arc<Relation>> = id_to_relations
+            .values()
+            .filter(|relation| {
+                self.in_region(ElementId::Relation(relation.id))
+                    && filter.matches(MemberType::Relation, relation.as_ref())
+            })
+            .collect();
+        let clipped = self.clips();
+        for relation in matching {
+            for relation_id in nested_relation_ids(relation, id_to_relations) {
+                if clipped && !self.in_region(ElementId::Relation(relation_id))
+                    || !self.relations.insert(relation_id)
+                {
+                    continue;
+                }
+                for member in &id_to_relations[&relation_id].member {
+                    if clipped && !self.in_region(member.element_id()) {
+                        continue;
+                    }
+                    match member.member_type {
+                        MemberType::Node => self.nodes.insert(member.member_ref),
+                        MemberType::Way => self.ways.insert(member.member_ref),
+                        // nested relations are among the resolved ids
+                        MemberType::Relation => continue,
+                    };
+                }
+            }
+        }
+    }
+
+    /// selects the way, with its nodes, when it matches or a selected relation references it.
+    /// Returns whether the way is selected
+    pub fn select_way(&mut self, filter: &Filter, way: &Way) -> bool {
+        let selected = self.ways.contains(&way.id)
+            || self.in_region(ElementId::Way(way.id)) && filter.matches(MemberType::Way, way);
+        if selected {
+            self.ways.insert(way.id);
+            let clipped = self.clips();
+            for nd in &way.nd {
+                if !clipped || self.in_region(ElementId::Node(nd.reference)) {
+                    self.nodes.insert(nd.reference);
+                }
+            }
+        }
+        selected
+    }
+
+    /// selects the node when it matches or a selected way or relation references it. Returns
+    /// whether the node is selected
+    pub fn select_node(&mut self, filter: &Filter, node: &Node) -> bool {
+        let selected = self.nodes.contains(&node.id)
+            || self.in_region(ElementId::Node(node.id)) && filter.matches(MemberType::Node, node);
+        if selected {
+            self.nodes.insert(node.id);
+        }
+        selected
+    }
+
+    /// drops the nodes of a selected way outside of the region, for clipped selections
+    pub fn clip_way(&self, way: &mut Way) {
+        way.nd
+            .retain(|nd| self.in_region(ElementId::Node(nd.reference)));
+    }
+
+    /// drops the members of a selected relation that are not selected, for clipped selections
+    pub fn clip_relation(&self, relation: &mut Relation) {
+        relation.member.retain(|member| {
+            let ids = match member.member_type {
+                MemberType::Node => &self.nodes,
+                MemberType::Way => &self.ways,
+                MemberType::Relation => &self.relations,
+            };
+            ids.contains(&member.member_ref)
+        });
+    }
+}
+
+/// elements of the dataset matching the filter, with the ways and nodes they reference and the
+/// members of relations, nested relations included
+pub fn filter_osm(osm: &Osm, filter: &Filter) -> Osm {
+    extract_osm(osm, filter, Selection::default())
+}
+
+/// elements of the dataset in the region matching the filter, elements crossing the border are
+/// complete or clipped according to the strategy
+pub fn clip_osm(osm: &Osm, filter: &Filter, region: Region, strategy: Strategy) -> Osm {
+    let bounds = region.bounds();
+    let mut extract = extract_osm(osm, filter, Selection::within(region, strategy));
+    extract.bounds = Some(bounds);
+    extract
+}
+
+fn extract_osm(osm: &Osm, filter: &Filter, mut selection: Selection) -> Osm {
+    let id_to_relations: HashMap<u64, Arc<Relation>> = osm
+        .relation
+        .iter()
+        .map(|relation| (relation.id, relation.clone()))
+        .collect();
+    if selection.has_region() {
+        osm.node.iter().for_each(|node| selection.locate_node(node));
+        osm.way.iter().for_each(|way| selection.locate_way(way));
+        selection.locate_relations(&id_to_relations);
+    }
+    selection.select_relations(filter, &id_to_relations);
+
+    let mut way: Vec<Arc<Way>> = osm
+        .way
+        .iter()
+        .filter(|way| selection.select_way(filter, way))
+        .cloned()
+        .collect();
+    let node = osm
+        .node
+        .iter()
+        .filter(|node| selection.select_node(filter, node))
+        .cloned()
+        .collect();
+    let mut relation: Vec<Arc<Relation>> = osm
+        .relation
+        .iter()
+        .filter(|relation| selection.relations.contains(&relation.id))
+        .cloned()
+        .collect();
+    if selection.clips() {
+        way.iter_mut()
+            .for_each(|way| selection.clip_way(Arc::make_mut(way)));
+        relation
+            .iter_mut()
+            .for_each(|relation| selection.clip_relation(Arc::make_mut(relation)));
+    }
+    Osm {
+        version: osm.version.clone(),
+        generator: osm.generator.clone(),
+        bounds: None,
+        relation,
+        way,
+        node,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        dataset::MemoryDataset, stream::XmlReader, Bounds, Member, MemberType, Osm, Relation,
+    };
+
+    use super::{clip_osm, filter_osm, Region, Strategy};
+
+    const OSM_XML: &str = r#"<osm version="0.6">
+  <node id="1" lat="47.0" lon="28.8"/>
+  <node id="2" lat="47.1" lon="28.8"/>
+  <node id="3" lat="47.1" lon="28.9"/>
+  <node id="4" lat="47.2" lon="28.9"><tag k="amenity" v="cafe"/></node>
+  <node id="5" lat="47.2" lon="29.0"/>
+  <node id="6" lat="47.3" lon="29.0"/>
+  <way id="10"><nd ref="1"/><nd ref="2"/><nd ref="3"/><nd ref="1"/><tag k="leisure" v="park"/></way>
+  <way id="11"><nd ref="5"/><nd ref="6"/></way>
+  <way id="12"><nd ref="2"/><nd ref="6"/><tag k="highway" v="service"/></way>
+  <relation id="20">
+    <member type="way" ref="11" role="outer"/>
+    <member type="relation" ref="21" role=""/>
+    <tag k="leisure" v="park"/>
+  </relation>
+  <relation id="21">
+    <member type="node" ref="5" role="label"/>
+  </relation>
+  <relation id="22">
+    <member type="way" ref="12" role=""/>
+  </relation>
+</osm>"#;
+
+    fn read_osm() -> Osm {
+        let mut osm = Osm::default();
+        XmlReader::new(OSM_XML.as_bytes()).for_each(|element| osm.push(element.unwrap()));
+        osm
+    }
+
+    fn ids(extract: &Osm) -> (Vec<u64>, Vec<u64>, Vec<u64>) {
+        (
+            extract.node.iter().map(|node| node.id).collect(),
+            extract.way.iter().map(|way| way.id).collect(),
+            extract
+                .relation
+                .iter()
+                .map(|relation| relation.id)
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn filter_with_complete_references() {
+        let osm = read_osm();
+        let filtered = |filter: &str| ids(&filter_osm(&osm, &filter.parse().unwrap()));
+
+        assert_eq!(
+            filtered("way[leisure=park]"),
+            (vec![1, 2, 3], vec![10], vec![])
+        );
+        assert_eq!(filtered("node[amenity]"), (vec![4], vec![], vec![]));
+        assert_eq!(
+            filtered("relation[leisure=park]"),
+            (vec![5, 6], vec![11], vec![20, 21])
+        );
+        assert_eq!(
+            filtered("[leisure=park], node[amenity]"),
+            (vec![1, 2, 3, 4, 5, 6], vec![10, 11], vec![20, 21])
+        );
+        assert_eq!(filtered("way[highway=primary]"), (vec![], vec![], vec![]));
+    }
+
+    #[test]
+    fn extract_region() {
+        let osm = read_osm();
+        let bounds = Bounds {
+            minlat: 47.05,
+            minlon: 28.75,
+            maxlat: 47.25,
+            maxlon: 28.95,
+        };
+        let extract = |filter: &str, strategy| {
+            let region = Region::Bounds(bounds.clone());
+            clip_osm(&osm, &filter.parse().unwrap(), region, strategy)
+        };
+
+        // way 12 has one node inside, relation 22 is inside through it
+        let complete = extract("*", Strategy::Complete);
+        assert_eq!(
+            ids(&complete),
+            (vec![1, 2, 3, 4, 6], vec![10, 12], vec![22])
+        );
+        assert_eq!(complete.bounds, Some(bounds.clone()));
+        assert_eq!(complete.way[0].nd.len(), 4);
+
+        let clipped = extract("*", Strategy::Clipped);
+        assert_eq!(ids(&clipped), (vec![2, 3, 4], vec![10], vec![]));
+        let nodes: Vec<u64> = clipped.way[0].nd.iter().map(|nd| nd.reference).collect();
+        assert_eq!(nodes, vec![2, 3]);
+
+        assert_eq!(
+            ids(&extract("node[amenity], relation", Strategy::Complete)),
+            (vec![2, 4, 6], vec![12], vec![22])
+        );
+        assert_eq!(
+            ids(&extract("relation", Strategy::Clipped)),
+            (vec![], vec![], vec![])
+        );
+
+        // the closed way 10 starts and ends at node 1, the only one inside
+        let corner = Region::Bounds(Bounds {
+            minlat: 46.95,
+            minlon: 28.75,
+            maxlat: 47.05,
+            maxlon: 28.85,
+        });
+        assert_eq!(
+            ids(&clip_osm(
+                &osm,
+                &"*".parse().unwrap(),
+                corner,
+                Strategy::Clipped
+            )),
+            (vec![1], vec![], vec![])
+        );
+    }
+
+    #[test]
+    fn boundary_region() {
+        let osm = read_osm();
+        let boundary = Relation {
+            id: 30,
+            member: vec![Member {
+                member_type: MemberType::Way,
+                member_ref: 10,
+                role: "outer".to_string(),
+                tag: None,
+            }],
+            ..Default::default()
+        };
+        let region = Region::boundary(&boundary, &MemoryDataset::from(&osm)).unwrap();
+
+        assert!(region.contains(47.08, 28.82));
+        assert!(region.contains(47.1, 28.85));
+        assert!(!region.contains(47.02, 28.88));
+        assert!(!region.contains(47.2, 28.9));
+        assert_eq!(
+            region.bounds(),
+            Bounds {
+                minlat: 47.0,
+                minlon: 28.8,
+                maxlat: 47.1,
+                maxlon: 28.9,
+            }
+        );
+
+        let empty = Relation {
+            id: 31,
+            ..Default::default()
+        };
+        assert_eq!(
+            Region::boundary(&empty, &MemoryDataset::from(&osm))
+                .unwrap_err()
+                .to_string(),
+            "relation 31 has no area"
+        );
+    }
+}
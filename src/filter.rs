@@ -0,0 +1,371 @@
+use std::{fmt, str::FromStr};
+
+use regex::Regex;
+
+use crate::{Element, Error, MemberType, Result, Symbol, Tagged};
+
+/// comparison of a tag value with a number, values that are not numbers never match
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+#[derive(Clone, Debug)]
+enum Test {
+    Exists(Symbol),
+    Equals(Symbol, Symbol),
+    NotEquals(Symbol, Symbol),
+    Matches(Symbol, Regex),
+    Compare(Symbol, Comparison, f64),
+}
+
+impl Test {
+    fn matches(&self, element: &impl Tagged) -> bool {
+        match self {
+            Test::Exists(key) => element.has_key(key),
+            Test::Equals(key, value) => element.has_tag(key, value),
+            // elements without the key do not have the value either
+            Test::NotEquals(key, value) => element.tag(key) != Some(*value),
+            Test::Matches(key, regex) => {
+                element.tag(key).is_some_and(|value| regex.is_match(&value))
+            }
+            Test::Compare(key, comparison, number) => element
+                .tag(key)
+                .and_then(|value| value.trim().parse::<f64>().ok())
+                .is_some_and(|value| match comparison {
+                    Comparison::Less => value < *number,
+                    Comparison::LessOrEqual => value <= *number,
+                    Comparison::Greater => value > *number,
+                    Comparison::GreaterOrEqual => value >= *number,
+                }),
+        }
+    }
+}
+
+/// `[test]`, or `[!test]` for the elements the test does not match
+#[derive(Clone, Debug)]
+struct Condition {
+    negated: bool,
+    test: Test,
+}
+
+/// element type followed by conditions that all have to match
+#[derive(Clone, Debug)]
+struct Selector {
+    /// `None` matches elements of any type
+    element_type: Option<MemberType>,
+    conditions: Vec<Condition>,
+}
+
+/// tag filter, parsed from selectors separated by commas that each select an element type and
+/// list conditions on tags:
+///
+/// ```text
+/// relation[leisure=park][name~"^Parcul"], way[highway][lanes>=2][!oneway]
+/// ```
+///
+/// An element matches the filter when it matches any of the selectors. Selectors start with
+/// `node`, `way`, `relation` or `*` for any type, the type can be left out when conditions
+/// follow. A condition is `[key]` for elements with the key, `[key=value]`, `[key!=value]`,
+/// `[key~regex]`, `[key!~regex]` or a numeric comparison `<`, `<=`, `>`, `>=`. `[!...]` negates
+/// the condition inside. Keys and values with other characters than letters, digits and `_:.-`
+/// are written in double quotes
+#[derive(Clone, Debug)]
+pub struct Filter {
+    source: String,
+    selectors: Vec<Selector>,
+}
+
+impl Filter {
+    /// whether the element of the given type matches
+    pub fn matches(&self, element_type: MemberType, element: &impl Tagged) -> bool {
+        self.selectors.iter().any(|selector| {
+            selector
+                .element_type
+                .is_none_or(|selected| selected == element_type)
+                && selector
+                    .conditions
+                    .iter()
+                    .all(|condition| condition.test.matches(element) != condition.negated)
+        })
+    }
+
+    pub fn matches_element(&self, element: &Element) -> bool {
+        match element {
+            Element::Node(node) => self.matches(MemberType::Node, node),
+            Element::Way(way) => self.matches(MemberType::Way, way),
+            Element::Relation(relation) => self.matches(MemberType::Relation, relation),
+        }
+    }
+
+    /// whether elements of the type can match, so that the elements of other types do not have
+    /// to be read
+    pub fn selects(&self, element_type: MemberType) -> bool {
+        self.selectors.iter().any(|selector| {
+            selector
+                .element_type
+                .is_none_or(|selected| selected == element_type)
+        })
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl FromStr for Filter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parser = Parser {
+            input: s,
+            position: 0,
+        };
+        let mut selectors = vec![parser.selector()?];
+        while parser.eat(",") {
+            selectors.push(parser.selector()?);
+        }
+        parser.skip_whitespace();
+        if parser.position < s.len() {
+            return Err(parser.error("expected `,` or the end of the filter"));
+        }
+        Ok(Filter {
+            source: s.to_string(),
+            selectors,
+        })
+    }
+}
+
+/// characters of keys and values that are not quoted
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | ':' | '.' | '-')
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn rest(&self) -> &str {
+        &self.input[self.position..]
+    }
+
+    fn error(&self, message: &str) -> Error {
+        Error::InvalidFilter {
+            column: self.input[..self.position].chars().count() + 1,
+            message: message.to_string(),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    /// consumes the token if the input continues with it
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(token) {
+            self.position += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn selector(&mut self) -> Result<Selector> {
+        let any = self.eat("*");
+        let element_type = if any {
+            None
+        } else {
+            let word: String = self.rest().chars().take_while(|c| is_word(*c)).collect();
+            let element_type = match word.as_str() {
+                "" => None,
+                "node" => Some(MemberType::Node),
+                "way" => Some(MemberType::Way),
+                "relation" => Some(MemberType::Relation),
+                _ => return Err(self.error("expected node, way, relation, * or `[`")),
+            };
+            self.position += word.len();
+            element_type
+        };
+
+        let mut conditions = Vec::new();
+        while self.eat("[") {
+            conditions.push(self.condition()?);
+        }
+        if !any && element_type.is_none() && conditions.is_empty() {
+            return Err(self.error("expected an element type or a condition"));
+        }
+        Ok(Selector {
+            element_type,
+            conditions,
+        })
+    }
+
+    fn condition(&mut self) -> Result<Condition> {
+        let mut negated = self.eat("!");
+        let key = Symbol::new(&self.string("a key")?);
+        let test = if self.eat("]") {
+            return Ok(Condition {
+                negated,
+                test: Test::Exists(key),
+            });
+        } else if self.eat("!=") {
+            Test::NotEquals(key, Symbol::new(&self.string("a value")?))
+        } else if self.eat("!~") {
+            negated = !negated;
+            self.regex(key)?
+        } else if self.eat("=") {
+            Test::Equals(key, Symbol::new(&self.string("a value")?))
+        } else if self.eat("~") {
+            self.regex(key)?
+        } else if self.eat("<=") {
+            Test::Compare(key, Comparison::LessOrEqual, self.number()?)
+        } else if self.eat("<") {
+            Test::Compare(key, Comparison::Less, self.number()?)
+        } else if self.eat(">=") {
+            Test::Compare(key, Comparison::GreaterOrEqual, self.number()?)
+        } else if self.eat(">") {
+            Test::Compare(key, Comparison::Greater, self.number()?)
+        } else {
+            return Err(self.error("expected `]`, `=`, `!=`, `~`, `!~`, `<`, `<=`, `>` or `>=`"));
+        };
+        if !self.eat("]") {
+            return Err(self.error("expected `]`"));
+        }
+        Ok(Condition { negated, test })
+    }
+
+    fn regex(&mut self, key: Symbol) -> Result<Test> {
+        let start = self.position;
+        let pattern = self.string("a regular expression")?;
+        Regex::new(&pattern)
+            .map(|regex| Test::Matches(key, regex))
+            .map_err(|error| {
+                self.position = start;
+                self.error(&error.to_string())
+            })
+    }
+
+    fn number(&mut self) -> Result<f64> {
+        let start = self.position;
+        let value = self.string("a number")?;
+        value.parse().map_err(|_| {
+            self.position = start;
+            self.error("expected a number")
+        })
+    }
+
+    /// word or string in double quotes, `\"` and `\\` escape quotes and backslashes
+    fn string(&mut self, what: &str) -> Result<String> {
+        self.skip_whitespace();
+        let mut chars = self.rest().char_indices();
+        match chars.next() {
+            Some((_, '"')) => {
+                let mut value = String::new();
+                let mut escaped = false;
+                for (index, c) in chars {
+                    match c {
+                        '\\' if !escaped => escaped = true,
+                        '"' if !escaped => {
+                            self.position += index + 1;
+                            return Ok(value);
+                        }
+                        _ => {
+                            value.push(c);
+                            escaped = false;
+                        }
+                    }
+                }
+                Err(self.error("unterminated string"))
+            }
+            Some((_, c)) if is_word(c) => {
+                let value: String = self.rest().chars().take_while(|c| is_word(*c)).collect();
+                self.position += value.len();
+                Ok(value)
+            }
+            _ => Err(self.error(&format!("expected {}", what))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{MemberType, Relation, Tag, Way};
+
+    use super::Filter;
+
+    fn tags(tags: &[(&str, &str)]) -> Option<Vec<Tag>> {
+        Some(
+            tags.iter()
+                .map(|(k, v)| Tag {
+                    k: (*k).into(),
+                    v: (*v).into(),
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn parse_and_match_filters() {
+        let park = Relation {
+            tag: tags(&[("leisure", "park"), ("name", "Parcul Valea Morilor")]),
+            ..Default::default()
+        };
+        let road = Way {
+            tag: tags(&[("highway", "primary"), ("lanes", "3"), ("oneway", "yes")]),
+            ..Default::default()
+        };
+        let matches = |filter: &str| {
+            let filter: Filter = filter.parse().unwrap();
+            (
+                filter.matches(MemberType::Relation, &park),
+                filter.matches(MemberType::Way, &road),
+            )
+        };
+
+        assert_eq!(
+            matches(r#"relation[leisure=park][name~"^Parcul"]"#),
+            (true, false)
+        );
+        assert_eq!(
+            matches(r#"relation[leisure=park][name~"^Grădina"]"#),
+            (false, false)
+        );
+        assert_eq!(matches("[leisure]"), (true, false));
+        assert_eq!(matches("[!leisure]"), (false, true));
+        assert_eq!(matches("*"), (true, true));
+        assert_eq!(matches("way[highway][lanes>=3]"), (false, true));
+        assert_eq!(matches("way[lanes<3]"), (false, false));
+        assert_eq!(matches("way[name!=x]"), (false, true));
+        assert_eq!(matches("way[oneway!~\"yes|1\"]"), (false, false));
+        assert_eq!(matches("node, way[highway=primary]"), (false, true));
+        assert_eq!(
+            matches(r#"relation[leisure=park], way[!oneway]"#),
+            (true, false)
+        );
+    }
+
+    #[test]
+    fn reject_invalid_filters() {
+        for (filter, error) in [
+            ("area[leisure=park]", "invalid filter at column 1: expected node, way, relation, * or `[`"),
+            ("way[highway", "invalid filter at column 12: expected `]`, `=`, `!=`, `~`, `!~`, `<`, `<=`, `>` or `>=`"),
+            ("way[lanes>two]", "invalid filter at column 11: expected a number"),
+            ("way[name=\"x]", "invalid filter at column 10: unterminated string"),
+            ("way[name] x", "invalid filter at column 11: expected `,` or the end of the filter"),
+            ("", "invalid filter at column 1: expected an element type or a condition"),
+        ] {
+            assert_eq!(filter.parse::<Filter>().unwrap_err().to_string(), error);
+        }
+        let error = "way[name~\"(\"]".parse::<Filter>().unwrap_err().to_string();
+        assert!(error.starts_with("invalid filter at column 10: regex parse error"));
+    }
+}
@@ -0,0 +1,492 @@
+use std::io::{self, Read, Write};
+
+use ::geojson::{feature::Id, Feature, GeoJson, Geometry, JsonObject, JsonValue, Position, Value};
+use log::debug;
+
+use crate::{
+    dataset::MemoryDataset,
+    geometry::{relation_to_multipolygon, way_to_line_string, way_to_polygon},
+    validate::is_area,
+    Bounds, Element, Error, Member, MemberType, Nd, Node, Osm, Relation, Result, Symbol, Tag,
+    Tagged, Way,
+};
+
+/// largest id JavaScript clients of the JSON and GeoJSON outputs read exactly, 2^53 - 1
+const MAX_SYNTHETIC_ID: u64 = (1 << 53) - 1;
+/// ids a document counts down through
+const BLOCK_SIZE: u64 = 1 << 32;
+/// blocks between 2^52 and 2^53, far above the ids OSM has given out
+const BLOCKS: u64 = 1 << 20;
+
+/// block of the range reserved for synthetic ids the id is in, `None` for any other id
+pub fn synthetic_block(id: u64) -> Option<u64> {
+    (MAX_SYNTHETIC_ID - BLOCKS * BLOCK_SIZE < id && id <= MAX_SYNTHETIC_ID)
+        .then(|| (MAX_SYNTHETIC_ID - id) / BLOCK_SIZE)
+}
+
+/// ids of the elements created from the features of a document, counted down from the top of
+/// the range between 2^52 and 2^53 that is reserved for them. Each document counts down in its
+/// own block of the range so that several GeoJSON layers can be merged into one dataset
+struct SyntheticIds {
+    block: u64,
+    next: u64,
+    /// highest id of the block below
+    end: u64,
+}
+
+impl SyntheticIds {
+    fn new(block: u64) -> Result<Self> {
+        if block >= BLOCKS {
+            return Err(Error::SyntheticIdsExhausted { block });
+        }
+        let next = MAX_SYNTHETIC_ID - block * BLOCK_SIZE;
+        Ok(SyntheticIds {
+            block,
+            next,
+            end: next - BLOCK_SIZE,
+        })
+    }
+
+    fn next(&mut self) -> Result<u64> {
+        if self.next == self.end {
+            return Err(Error::SyntheticIdsExhausted { block: self.block });
+        }
+        let id = self.next;
+        self.next -= 1;
+        Ok(id)
+    }
+}
+
+/// property values that are not strings are written as JSON, null properties are dropped
+fn tags(properties: Option<&JsonObject>) -> Vec<Tag> {
+    properties
+        .into_iter()
+        .flatten()
+        .filter(|(_, value)| !value.is_null())
+        .map(|(key, value)| Tag {
+            k: Symbol::new(key),
+            v: match value {
+                JsonValue::String(value) => Symbol::new(value),
+                value => Symbol::new(&value.to_string()),
+            },
+        })
+        .collect()
+}
+
+/// builds the nodes, ways and relations of GeoJSON geometries
+struct Converter {
+    ids: SyntheticIds,
+    osm: Osm,
+}
+
+impl Converter {
+    fn node(&mut self, position: &Position, tag: Option<Vec<Tag>>) -> Result<u64> {
+        // positions with less than two coordinates are rejected by the parser
+        let (lon, lat) = (position[0], position[1]);
+        let id = self.ids.next()?;
+        self.osm.push(Element::Node(Node {
+            id,
+            lat,
+            lon,
+            tag,
+            ..Default::default()
+        }));
+        Ok(id)
+    }
+
+    /// way through the positions, the first node of a closed ring is also its last
+    fn way(&mut self, positions: &[Position], tag: Option<Vec<Tag>>) -> Result<u64> {
+        let closed = positions.len() > 2 && positions.first() == positions.last();
+        let open = if closed {
+            &positions[..positions.len() - 1]
+        } else {
+            positions
+        };
+        let mut nodes = open
+            .iter()
+            .map(|position| self.node(position, None))
+            .collect::<Result<Vec<u64>>>()?;
+        if closed {
+            nodes.push(nodes[0]);
+        }
+        let id = self.ids.next()?;
+        self.osm.push(Element::Way(Way {
+            id,
+            nd: nodes
+                .into_iter()
+                .map(|reference| Nd { reference })
+                .collect(),
+            tag,
+            ..Default::default()
+        }));
+        Ok(id)
+    }
+
+    /// polygons without holes are closed ways, others multipolygon relations of their rings
+    fn polygons(&mut self, polygons: &[Vec<Vec<Position>>], mut tags: Vec<Tag>) -> Result<()> {
+        if let [polygon] = polygons {
+            if let [ring] = &polygon[..] {
+                if !tags.iter().any(|tag| tag.k == Symbol::AREA) {
+                    tags.push(Tag {
+                        k: Symbol::AREA,
+                        v: Symbol::YES,
+                    });
+                }
+                self.way(ring, Some(tags))?;
+                return Ok(());
+            }
+        }
+        let mut member = Vec::new();
+        for polygon in polygons {
+            for (index, ring) in polygon.iter().enumerate() {
+                member.push(Member {
+                    member_type: MemberType::Way,
+                    member_ref: self.way(ring, None)?,
+                    role: if index == 0 { "outer" } else { "inner" }.to_string(),
+                    tag: None,
+                });
+            }
+        }
+        // a `type` property would hide the relation type from the renderer and the writers, it is
+        // kept as `@type`
+        let relation_type = Symbol::new("type");
+        for tag in tags.iter_mut().filter(|tag| tag.k == relation_type) {
+            tag.k = Symbol::new("@type");
+        }
+        tags.push(Tag {
+            k: relation_type,
+            v: Symbol::new("multipolygon"),
+        });
+        self.osm.push(Element::Relation(Relation {
+            id: self.ids.next()?,
+            member,
+            tag: Some(tags),
+            ..Default::default()
+        }));
+        Ok(())
+    }
+
+    /// elements of the geometry, the parts of multi geometries each get the tags
+    fn geometry(&mut self, value: &Value, tags: Vec<Tag>) -> Result<()> {
+        match value {
+            Value::Point(position) => {
+                self.node(position, Some(tags))?;
+            }
+            Value::MultiPoint(positions) => {
+                for position in positions {
+                    self.node(position, Some(tags.clone()))?;
+                }
+            }
+            Value::LineString(positions) => {
+                self.way(positions, Some(tags))?;
+            }
+            Value::MultiLineString(lines) => {
+                for positions in lines {
+                    self.way(positions, Some(tags.clone()))?;
+                }
+            }
+            Value::Polygon(polygon) => self.polygons(std::slice::from_ref(polygon), tags)?,
+            Value::MultiPolygon(polygons) => self.polygons(polygons, tags)?,
+            Value::GeometryCollection(geometries) => {
+                for geometry in geometries {
+                    self.geometry(&geometry.value, tags.clone())?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// converts a GeoJSON document into elements. Points become nodes, lines ways and polygons
+/// closed ways tagged `area=yes`, or multipolygon relations when they have holes or several
+/// parts. The properties of a feature are the tags of its elements, the ids are synthetic and
+/// counted down in `block`. Features without a geometry are skipped
+pub fn read_geojson<R: Read>(mut reader: R, block: u64) -> Result<Osm> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    let document = GeoJson::from_reader(bytes.as_slice())
+        .map_err(|error| Error::InvalidGeoJson(error.to_string()))?;
+    let features = match document {
+        GeoJson::FeatureCollection(collection) => collection.features,
+        GeoJson::Feature(feature) => vec![feature],
+        GeoJson::Geometry(geometry) => vec![Feature::from(geometry)],
+    };
+
+    let mut converter = Converter {
+        ids: SyntheticIds::new(block)?,
+        osm: Osm::default(),
+    };
+    for feature in &features {
+        match &feature.geometry {
+            Some(geometry) => {
+                converter.geometry(&geometry.value, tags(feature.properties.as_ref()))?
+            }
+            None => debug!("skipped a feature without geometry"),
+        }
+    }
+    Ok(converter.osm)
+}
+
+fn properties(tags: &[Tag]) -> JsonObject {
+    tags.iter()
+        .map(|tag| (tag.k.to_string(), JsonValue::from(tag.v.as_str())))
+        .collect()
+}
+
+/// incrementally writes elements as a GeoJSON FeatureCollection. Tagged nodes are written as
+/// points, tagged ways as lines or, when they are areas, as polygons, and multipolygon and
+/// boundary relations as multipolygons assembled from their ways. The tags are the properties of
+/// the features and `way/10` their ids. Nodes and ways are kept in memory until the end so that
+/// the ways and relations written after them can be assembled
+pub struct GeoJsonWriter<W: Write> {
+    writer: W,
+    dataset: MemoryDataset,
+    empty: bool,
+}
+
+impl<W: Write> GeoJsonWriter<W> {
+    pub fn new(mut writer: W, bounds: Option<&Bounds>) -> io::Result<Self> {
+        writer.write_all(b"{\"type\":\"FeatureCollection\",")?;
+        if let Some(bounds) = bounds {
+            let bbox = [bounds.minlon, bounds.minlat, bounds.maxlon, bounds.maxlat];
+            writer.write_all(b"\"bbox\":")?;
+            serde_json::to_writer(&mut writer, &bbox)?;
+            writer.write_all(b",")?;
+        }
+        writer.write_all(b"\"features\":[")?;
+        Ok(Self {
+            writer,
+            dataset: MemoryDataset::default(),
+            empty: true,
+        })
+    }
+
+    fn geometry(&self, element: &Element) -> Result<Option<Geometry>> {
+        let value = match element {
+            Element::Node(node) => (&geo::Point::new(node.lon, node.lat)).into(),
+            Element::Way(way) => {
+                let closed = way.nd.len() >= 4
+                    && way.nd.first().map(|nd| nd.reference)
+                        == way.nd.last().map(|nd| nd.reference);
+                if closed && is_area(way) {
+                    (&way_to_polygon(way, &self.dataset)?).into()
+                } else {
+                    (&way_to_line_string(way, &self.dataset)?).into()
+                }
+            }
+            Element::Relation(relation) => match relation.tag("type").as_deref() {
+                Some("multipolygon" | "boundary") => {
+                    (&relation_to_multipolygon(relation, &self.dataset)?).into()
+                }
+                _ => return Ok(None),
+            },
+        };
+        Ok(Some(Geometry::new(value)))
+    }
+
+    pub fn write(&mut self, element: &Element) -> io::Result<()> {
+        let (element_type, id, tags) = match element {
+            Element::Node(node) => (MemberType::Node, node.id, node.tags()),
+            Element::Way(way) => (MemberType::Way, way.id, way.tags()),
+            Element::Relation(relation) => (MemberType::Relation, relation.id, relation.tags()),
+        };
+        if !matches!(element, Element::Relation(_)) {
+            self.dataset.insert(element);
+        }
+        // untagged nodes and ways are part of the geometry of other features
+        if tags.is_empty() {
+            return Ok(());
+        }
+        let geometry = match self.geometry(element) {
+            Ok(Some(geometry)) => geometry,
+            Ok(None) => return Ok(()),
+            Err(error) => {
+                debug!("{} {} is not written: {}", element_type, id, error);
+                return Ok(());
+            }
+        };
+        let feature = Feature {
+            bbox: None,
+            geometry: Some(geometry),
+            id: Some(Id::String(format!("{}/{}", element_type, id))),
+            properties: Some(properties(tags)),
+            foreign_members: None,
+        };
+
+        self.writer
+            .write_all(if self.empty { b"\n" } else { b",\n" })?;
+        self.empty = false;
+        serde_json::to_writer(&mut self.writer, &feature)?;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.write_all(b"\n]}\n")?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::{json, Value};
+
+    use crate::{
+        classes::registry,
+        stream::{ElementReader, XmlReader},
+        Element, Tagged, Way,
+    };
+
+    use super::{read_geojson, synthetic_block, GeoJsonWriter, SyntheticIds, BLOCKS};
+
+    const OSM_XML: &str = r#"<osm version="0.6">
+  <bounds minlat="47.0" minlon="28.8" maxlat="47.1" maxlon="28.9"/>
+  <node id="1" lat="47.0" lon="28.8"/>
+  <node id="2" lat="47.1" lon="28.8"><tag k="amenity" v="cafe"/></node>
+  <node id="3" lat="47.1" lon="28.9"/>
+  <way id="10"><nd ref="1"/><nd ref="2"/><nd ref="3"/><nd ref="1"/><tag k="building" v="yes"/></way>
+  <way id="11"><nd ref="1"/><nd ref="3"/><tag k="highway" v="primary"/></way>
+  <way id="12"><nd ref="2"/><nd ref="3"/><nd ref="1"/><nd ref="2"/></way>
+  <way id="13"><nd ref="2"/><nd ref="4"/><tag k="highway" v="service"/></way>
+  <relation id="100"><member type="way" ref="12" role="outer"/><tag k="type" v="multipolygon"/><tag k="leisure" v="park"/></relation>
+  <relation id="101"><member type="way" ref="11" role=""/><tag k="type" v="route"/></relation>
+</osm>"#;
+
+    #[test]
+    fn write_features() {
+        let mut reader = XmlReader::new(OSM_XML.as_bytes());
+        let elements = reader
+            .by_ref()
+            .collect::<std::io::Result<Vec<Element>>>()
+            .unwrap();
+        let mut writer = GeoJsonWriter::new(Vec::new(), reader.bounds()).unwrap();
+        elements
+            .iter()
+            .for_each(|element| writer.write(element).unwrap());
+        let json: Value = serde_json::from_slice(&writer.finish().unwrap()).unwrap();
+
+        assert_eq!(json["type"], "FeatureCollection");
+        assert_eq!(json["bbox"], json!([28.8, 47.0, 28.9, 47.1]));
+        let features = json["features"].as_array().unwrap();
+        // untagged nodes and ways, the way with a missing node and the route are left out
+        let ids: Vec<&str> = features
+            .iter()
+            .map(|feature| feature["id"].as_str().unwrap())
+            .collect();
+        assert_eq!(ids, vec!["node/2", "way/10", "way/11", "relation/100"]);
+        assert_eq!(
+            features[0]["geometry"],
+            json!({"type": "Point", "coordinates": [28.8, 47.1]})
+        );
+        assert_eq!(features[0]["properties"], json!({"amenity": "cafe"}));
+        assert_eq!(features[1]["geometry"]["type"], "Polygon");
+        assert_eq!(
+            features[2]["geometry"],
+            json!({"type": "LineString", "coordinates": [[28.8, 47.0], [28.9, 47.1]]})
+        );
+        assert_eq!(features[3]["geometry"]["type"], "MultiPolygon");
+        assert_eq!(
+            features[3]["properties"],
+            json!({"type": "multipolygon", "leisure": "park"})
+        );
+    }
+
+    const GEOJSON: &str = r#"{"type": "FeatureCollection", "features": [
+  {"type": "Feature", "geometry": {"type": "Point", "coordinates": [28.83, 47.02]},
+    "properties": {"name": "Stație", "capacity": 12, "covered": true, "note": null}},
+  {"type": "Feature", "geometry": {"type": "LineString", "coordinates": [[28.83, 47.02], [28.84, 47.03], [28.85, 47.03]]},
+    "properties": {"highway": "cycleway", "status": "planned"}},
+  {"type": "Feature", "geometry": {"type": "Polygon", "coordinates": [
+      [[28.80, 47.00], [28.90, 47.00], [28.90, 47.10], [28.80, 47.10], [28.80, 47.00]],
+      [[28.82, 47.02], [28.84, 47.02], [28.84, 47.04], [28.82, 47.04], [28.82, 47.02]]]},
+    "properties": {"landuse": "construction", "project": "Centru", "type": "renovation"}},
+  {"type": "Feature", "geometry": {"type": "Polygon", "coordinates": [
+      [[28.86, 47.06], [28.88, 47.06], [28.88, 47.08], [28.86, 47.06]]]},
+    "properties": {"leisure": "park"}},
+  {"type": "Feature", "geometry": null, "properties": {"name": "no geometry"}}
+]}"#;
+
+    #[test]
+    fn read_features() {
+        let osm = read_geojson(GEOJSON.as_bytes(), 0).unwrap();
+        assert_eq!(
+            (osm.node.len(), osm.way.len(), osm.relation.len()),
+            (1 + 3 + 8 + 3, 4, 1)
+        );
+        // reserved ids, exact as JavaScript numbers
+        assert!(osm
+            .node
+            .iter()
+            .all(|node| (1 << 52..1 << 53).contains(&node.id)));
+        assert_eq!(osm.node[0].id, (1 << 53) - 1);
+        assert_eq!(osm.node[1].id, osm.node[0].id - 1);
+        let again = read_geojson(GEOJSON.as_bytes(), 0).unwrap();
+        assert_eq!(again.way[0].id, osm.way[0].id);
+        // another layer counts down in the next block
+        let other = read_geojson(GEOJSON.as_bytes(), 1).unwrap();
+        assert_eq!(other.node[0].id, osm.node[0].id - (1 << 32));
+        assert_eq!(synthetic_block(osm.node[0].id), Some(0));
+        assert_eq!(synthetic_block(other.relation[0].id), Some(1));
+        assert_eq!(synthetic_block(1 << 52), Some(BLOCKS - 1));
+        assert_eq!(synthetic_block((1 << 52) - 1), None);
+
+        let station = &osm.node[0];
+        assert_eq!((station.lat, station.lon), (47.02, 28.83));
+        assert_eq!(station.tag("capacity").as_deref(), Some("12"));
+        assert_eq!(station.tag("covered").as_deref(), Some("true"));
+        assert!(!station.has_key("note"));
+
+        let classes = registry();
+        let names = |way: &Way| -> Vec<&str> {
+            classes
+                .classify(way)
+                .into_iter()
+                .map(|class| classes[class].name.as_str())
+                .collect()
+        };
+        let (lane, park) = (&osm.way[0], &osm.way[3]);
+        assert_eq!(names(lane), vec!["cycleway"]);
+        assert_eq!(names(park), vec!["park"]);
+        assert!(park.has_tag("area", "yes"));
+        assert_eq!(park.nd.len(), 4);
+        assert_eq!(park.nd[0].reference, park.nd[3].reference);
+
+        let project = &osm.relation[0];
+        // the `type` property is kept as `@type` next to the relation type
+        assert_eq!(project.tag("type").as_deref(), Some("multipolygon"));
+        assert_eq!(
+            project.tags().iter().filter(|tag| tag.k == "type").count(),
+            1
+        );
+        assert_eq!(project.tag("@type").as_deref(), Some("renovation"));
+        assert!(project.has_tag("project", "Centru"));
+        let roles: Vec<&str> = project
+            .member
+            .iter()
+            .map(|member| member.role.as_str())
+            .collect();
+        assert_eq!(roles, vec!["outer", "inner"]);
+
+        let error = read_geojson(r#"{"type": "Point", "coordinates": [28.8]}"#.as_bytes(), 0);
+        assert!(error
+            .err()
+            .unwrap()
+            .to_string()
+            .starts_with("invalid GeoJSON: "));
+    }
+
+    #[test]
+    fn exhaust_synthetic_ids() {
+        assert!(SyntheticIds::new(BLOCKS).is_err());
+        let mut ids = SyntheticIds::new(BLOCKS - 1).unwrap();
+        ids.next = ids.end + 1;
+        assert_eq!(ids.next().unwrap(), 1 << 52);
+        assert_eq!(
+            ids.next().err().unwrap().to_string(),
+            format!(
+                "no synthetic ids are left for the elements of GeoJSON layer {}",
+                BLOCKS - 1
+            )
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use std::sync::Arc;
+
+use geo::{Contains, Coord, LineString, MultiPolygon, Polygon};
+use log::debug;
+
+use crate::{dataset::Dataset, ElementId, Error, MemberType, Relation, Result, Way};
+
+/// ways joined end to end
+#[derive(Clone, PartialEq, Debug)]
+pub struct Ring {
+    /// ids of the joined ways, in ring order
+    pub ways: Vec<u64>,
+    pub nodes: Vec<u64>,
+}
+
+impl Ring {
+    pub fn is_closed(&self) -> bool {
+        self.nodes.len() > 3 && self.nodes.first() == self.nodes.last()
+    }
+}
+
+/// joins ways end to end into rings, ways are reversed where needed. Ways that can not be joined
+/// into a closed ring are returned as open rings
+pub fn assemble_rings<'a>(ways: impl IntoIterator<Item = &'a Way>) -> Vec<Ring> {
+    let mut ways: Vec<&Way> = ways.into_iter().filter(|way| !way.nd.is_empty()).collect();
+    // the ways are taken from the back, keep the first way first
+    ways.reverse();
+    let mut rings = Vec::new();
+    while let Some(way) = ways.pop() {
+        let mut ring = Ring {
+            ways: vec![way.id],
+            nodes: node_references(way),
+        };
+        while ring.nodes.first() != ring.nodes.last() || ring.nodes.len() < 2 {
+            let end = *ring.nodes.last().unwrap();
+            let Some(next) = ways.iter().rposition(|way| {
+                way.nd.first().unwrap().reference == end || way.nd.last().unwrap().reference == end
+            }) else {
+                break;
+            };
+            let next = ways.remove(next);
+            let mut nodes = node_references(next);
+            if nodes.first() != Some(&end) {
+                nodes.reverse();
+            }
+            ring.ways.push(next.id);
+            ring.nodes.extend(nodes.into_iter().skip(1));
+        }
+        rings.push(ring);
+    }
+    rings
+}
+
+/// longitude as x and latitude as y of the referenced nodes
+fn coordinates<D: Dataset + ?Sized>(
+    element: ElementId,
+    nodes: &[u64],
+    dataset: &D,
+) -> Result<LineString> {
+    nodes
+        .iter()
+        .map(|&node| {
+            dataset
+                .node(node)
+                .map(|(lat, lon)| Coord { x: lon, y: lat })
+                .ok_or(Error::MissingReference {
+                    element,
+                    missing: ElementId::Node(node),
+                })
+        })
+        .collect::<Result<Vec<Coord>>>()
+        .map(LineString::new)
+}
+
+fn node_references(way: &Way) -> Vec<u64> {
+    way.nd.iter().map(|nd| nd.reference).collect()
+}
+
+pub fn way_to_line_string<D: Dataset + ?Sized>(way: &Way, dataset: &D) -> Result<LineString> {
+    coordinates(ElementId::Way(way.id), &node_references(way), dataset)
+}
+
+/// polygon of an area way, the ring is closed if the way is not
+pub fn way_to_polygon<D: Dataset + ?Sized>(way: &Way, dataset: &D) -> Result<Polygon> {
+    Ok(Polygon::new(way_to_line_string(way, dataset)?, vec![]))
+}
+
+/// polygons of a multipolygon relation. Member ways with the `inner` role form holes, the other
+/// member ways the outer rings. Each hole is cut from the outer ring containing it, holes outside
+/// of every outer ring are dropped
+pub fn relation_to_multipolygon<D: Dataset + ?Sized>(
+    relation: &Relation,
+    dataset: &D,
+) -> Result<MultiPolygon> {
+    let element = ElementId::Relation(relation.id);
+    let (mut outer, mut inner) = (Vec::<Arc<Way>>::new(), Vec::<Arc<Way>>::new());
+    for member in &relation.member {
+        if member.member_type != MemberType::Way {
+            continue;
+        }
+        let way = dataset
+            .way(member.member_ref)
+            .ok_or(Error::MissingReference {
+                element,
+                missing: member.element_id(),
+            })?;
+        if member.role == "inner" {
+            inner.push(way);
+        } else {
+            outer.push(way);
+        }
+    }
+
+    let rings = |ways: Vec<Arc<Way>>| -> Result<Vec<LineString>> {
+        assemble_rings(ways.iter().map(Arc::as_ref))
+            .iter()
+            .map(|ring| {
+                if ring.is_closed() {
+                    coordinates(element, &ring.nodes, dataset)
+                } else {
+                    Err(Error::UnclosedRing { element })
+                }
+            })
+            .collect()
+    };
+
+    let mut polygons: Vec<Polygon> = rings(outer)?
+        .into_iter()
+        .map(|ring| Polygon::new(ring, vec![]))
+        .collect();
+    for ring in rings(inner)? {
+        match polygons.iter_mut().find(|polygon| polygon.contains(&ring)) {
+            Some(polygon) => polygon.interiors_push(ring),
+            None => debug!("{} has an inner ring outside of its outer rings", element),
+        }
+    }
+    Ok(MultiPolygon::new(polygons))
+}
+
+#[cfg(test)]
+mod test {
+    use geo::Area;
+
+    use crate::{dataset::MemoryDataset, stream::XmlReader, Osm};
+
+    use super::{assemble_rings, relation_to_multipolygon, way_to_line_string};
+
+    const OSM_XML: &str = r#"<osm version="0.6">
+  <node id="1" lat="0" lon="0"/>
+  <node id="2" lat="0" lon="4"/>
+  <node id="3" lat="4" lon="4"/>
+  <node id="4" lat="4" lon="0"/>
+  <node id="5" lat="1" lon="1"/>
+  <node id="6" lat="1" lon="2"/>
+  <node id="7" lat="2" lon="2"/>
+  <node id="8" lat="2" lon="1"/>
+  <way id="10"><nd ref="1"/><nd ref="2"/><nd ref="3"/></way>
+  <way id="11"><nd ref="1"/><nd ref="4"/><nd ref="3"/></way>
+  <way id="12"><nd ref="5"/><nd ref="6"/><nd ref="7"/><nd ref="8"/><nd ref="5"/></way>
+  <relation id="20">
+    <member type="way" ref="10" role="outer"/>
+    <member type="way" ref="11" role="outer"/>
+    <member type="way" ref="12" role="inner"/>
+    <tag k="type" v="multipolygon"/>
+  </relation>
+  <relation id="21">
+    <member type="way" ref="10" role="outer"/>
+  </relation>
+</osm>"#;
+
+    #[test]
+    fn convert_to_geometries() {
+        let mut osm = Osm::default();
+        XmlReader::new(OSM_XML.as_bytes()).for_each(|element| osm.push(element.unwrap()));
+        let dataset = MemoryDataset::from(&osm);
+
+        let line = way_to_line_string(&osm.way[0], &dataset).unwrap();
+        assert_eq!(line.0.len(), 3);
+        assert_eq!((line.0[1].x, line.0[1].y), (4.0, 0.0));
+
+        let multipolygon = relation_to_multipolygon(&osm.relation[0], &dataset).unwrap();
+        assert_eq!(multipolygon.0.len(), 1);
+        assert_eq!(multipolygon.0[0].interiors().len(), 1);
+        assert_eq!(multipolygon.unsigned_area(), 15.0);
+
+        assert!(relation_to_multipolygon(&osm.relation[1], &dataset).is_err());
+        let rings = assemble_rings([&*osm.way[1], &*osm.way[2], &*osm.way[0]]);
+        assert_eq!(rings.len(), 2);
+        assert_eq!(rings[0].ways, vec![11, 10]);
+        assert_eq!(rings[0].nodes, vec![1, 4, 3, 2, 1]);
+        assert!(rings.iter().all(|ring| ring.is_closed()));
+        let rings = assemble_rings([&*osm.way[0]]);
+        assert!(!rings[0].is_closed());
+    }
+}
@@ -0,0 +1,94 @@
+use std::path::Path;
+
+use crate::{
+    binary::read_binary,
+    compression::open_input,
+    geojson::read_geojson,
+    json::read_json,
+    mapped::MappedDataset,
+    pbf::PbfReader,
+    stream::{ElementReader, MemoryReader, XmlReader},
+    Osm, Result,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InputFormat {
+    Xml,
+    Pbf,
+    Cbor,
+    /// memory-mapped dataset
+    Mapped,
+    /// OSM JSON, as returned by the OSM API and Overpass
+    Json,
+    /// GeoJSON features converted into elements
+    GeoJson,
+}
+
+impl InputFormat {
+    /// format guessed from the file name, compression extensions are ignored so that
+    /// `extract.osm.gz` is read as XML and `osm.bin.zst` as CBOR, `.map` files are memory-mapped
+    pub fn from_path(path: &Path) -> Self {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let name = name
+            .strip_suffix(".gz")
+            .or(name.strip_suffix(".bz2"))
+            .or(name.strip_suffix(".zst"))
+            .unwrap_or(&name);
+        if name.ends_with(".pbf") {
+            InputFormat::Pbf
+        } else if name.ends_with(".bin") || name.ends_with(".cbor") {
+            InputFormat::Cbor
+        } else if name.ends_with(".map") {
+            InputFormat::Mapped
+        } else if name.ends_with(".geojson") {
+            InputFormat::GeoJson
+        } else if name.ends_with(".json") {
+            InputFormat::Json
+        } else {
+            InputFormat::Xml
+        }
+    }
+
+    /// whether `read_elements` streams the file, files of the other formats are loaded into
+    /// memory whole
+    pub fn is_streamed(self) -> bool {
+        matches!(self, InputFormat::Xml | InputFormat::Pbf)
+    }
+}
+
+/// streams the elements of an XML or PBF file, datasets, JSON and GeoJSON documents are loaded
+/// into memory first
+pub fn read_elements(path: impl AsRef<Path>) -> Result<Box<dyn ElementReader>> {
+    let path = path.as_ref();
+    match InputFormat::from_path(path) {
+        InputFormat::Xml => Ok(Box::new(XmlReader::new(open_input(path)?))),
+        InputFormat::Pbf => Ok(Box::new(PbfReader::new(open_input(path)?))),
+        InputFormat::Cbor | InputFormat::Mapped | InputFormat::Json | InputFormat::GeoJson => {
+            Ok(Box::new(MemoryReader::from(read_osm(path)?)))
+        }
+    }
+}
+
+/// reads a whole XML, PBF, CBOR, memory-mapped, JSON or GeoJSON file into memory
+pub fn read_osm(path: impl AsRef<Path>) -> Result<Osm> {
+    let path = path.as_ref();
+    match InputFormat::from_path(path) {
+        InputFormat::Cbor => read_binary(open_input(path)?).map(|(_, osm)| osm),
+        InputFormat::Mapped => Ok(MappedDataset::open(path)?.to_osm()),
+        InputFormat::Json => read_json(open_input(path)?),
+        InputFormat::GeoJson => read_geojson(open_input(path)?, 0),
+        InputFormat::Xml | InputFormat::Pbf => {
+            let mut reader = read_elements(path)?;
+            let mut osm = Osm::default();
+            for element in reader.by_ref() {
+                osm.push(element?);
+            }
+            osm.bounds = reader.bounds().cloned();
+            osm.generator = reader.generator().map(str::to_string);
+            Ok(osm)
+        }
+    }
+}
@@ -0,0 +1,405 @@
+use std::{
+    fmt,
+    io::{self, Read, Write},
+};
+
+use log::debug;
+use serde::{de, ser::SerializeMap, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    Bounds, Element, Member, MemberType, Metadata, Nd, Node, Osm, Relation, Result, Symbol, Tag,
+    Way, GENERATOR, OSM_VERSION,
+};
+
+/// tags written as a JSON object, in the order of the element
+struct Tags(Vec<Tag>);
+
+impl Serialize for Tags {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|tag| (tag.k, tag.v)))
+    }
+}
+
+struct TagsVisitor;
+
+impl<'de> de::Visitor<'de> for TagsVisitor {
+    type Value = Tags;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an object of tag keys and values")
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> std::result::Result<Tags, A::Error> {
+        let mut tags = Vec::new();
+        while let Some((k, v)) = map.next_entry::<Symbol, Symbol>()? {
+            tags.push(Tag { k, v });
+        }
+        Ok(Tags(tags))
+    }
+}
+
+impl<'de> Deserialize<'de> for Tags {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        deserializer.deserialize_map(TagsVisitor)
+    }
+}
+
+fn tags(tag: &Option<Vec<Tag>>) -> Option<Tags> {
+    tag.as_ref()
+        .filter(|tag| !tag.is_empty())
+        .map(|tag| Tags(tag.clone()))
+}
+
+/// metadata named like the attributes of the elements without the `@`, it is read with either
+/// name
+#[derive(Deserialize)]
+#[serde(transparent)]
+struct JsonMetadata(Metadata);
+
+impl Serialize for JsonMetadata {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let Metadata {
+            version,
+            timestamp,
+            changeset,
+            user,
+            uid,
+            visible,
+        } = &self.0;
+        let mut map = serializer.serialize_map(None)?;
+        if let Some(version) = version {
+            map.serialize_entry("version", version)?;
+        }
+        if let Some(timestamp) = timestamp {
+            map.serialize_entry("timestamp", timestamp)?;
+        }
+        if let Some(changeset) = changeset {
+            map.serialize_entry("changeset", changeset)?;
+        }
+        if let Some(user) = user {
+            map.serialize_entry("user", user)?;
+        }
+        if let Some(uid) = uid {
+            map.serialize_entry("uid", uid)?;
+        }
+        if let Some(visible) = visible {
+            map.serialize_entry("visible", visible)?;
+        }
+        map.end()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonMember {
+    #[serde(rename = "type")]
+    member_type: MemberType,
+    #[serde(rename = "ref")]
+    member_ref: u64,
+    role: String,
+}
+
+/// element of the `elements` array, typed by its `type` field
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum JsonElement {
+    Node {
+        id: u64,
+        lat: f64,
+        lon: f64,
+        #[serde(flatten)]
+        metadata: JsonMetadata,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        tags: Option<Tags>,
+    },
+    Way {
+        id: u64,
+        #[serde(flatten)]
+        metadata: JsonMetadata,
+        nodes: Vec<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        tags: Option<Tags>,
+    },
+    Relation {
+        id: u64,
+        #[serde(flatten)]
+        metadata: JsonMetadata,
+        members: Vec<JsonMember>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        tags: Option<Tags>,
+    },
+    /// Overpass also returns areas and other derived elements
+    #[serde(other, skip_serializing)]
+    Other,
+}
+
+impl From<&Element> for JsonElement {
+    fn from(element: &Element) -> Self {
+        match element {
+            Element::Node(node) => JsonElement::Node {
+                id: node.id,
+                lat: node.lat,
+                lon: node.lon,
+                metadata: JsonMetadata(node.metadata.clone()),
+                tags: tags(&node.tag),
+            },
+            Element::Way(way) => JsonElement::Way {
+                id: way.id,
+                metadata: JsonMetadata(way.metadata.clone()),
+                nodes: way.nd.iter().map(|nd| nd.reference).collect(),
+                tags: tags(&way.tag),
+            },
+            Element::Relation(relation) => JsonElement::Relation {
+                id: relation.id,
+                metadata: JsonMetadata(relation.metadata.clone()),
+                members: relation
+                    .member
+                    .iter()
+                    .map(|member| JsonMember {
+                        member_type: member.member_type,
+                        member_ref: member.member_ref,
+                        role: member.role.clone(),
+                    })
+                    .collect(),
+                tags: tags(&relation.tag),
+            },
+        }
+    }
+}
+
+impl JsonElement {
+    /// `None` for the elements that are not nodes, ways or relations
+    fn into_element(self) -> Option<Element> {
+        Some(match self {
+            JsonElement::Node {
+                id,
+                lat,
+                lon,
+                metadata,
+                tags,
+            } => Element::Node(Node {
+                id,
+                lat,
+                lon,
+                tag: tags.map(|tags| tags.0),
+                metadata: metadata.0,
+            }),
+            JsonElement::Way {
+                id,
+                metadata,
+                nodes,
+                tags,
+            } => Element::Way(Way {
+                id,
+                nd: nodes
+                    .into_iter()
+                    .map(|reference| Nd { reference })
+                    .collect(),
+                tag: tags.map(|tags| tags.0),
+                metadata: metadata.0,
+            }),
+            JsonElement::Relation {
+                id,
+                metadata,
+                members,
+                tags,
+            } => Element::Relation(Relation {
+                id,
+                member: members
+                    .into_iter()
+                    .map(|member| Member {
+                        member_type: member.member_type,
+                        member_ref: member.member_ref,
+                        role: member.role,
+                        tag: None,
+                    })
+                    .collect(),
+                tag: tags.map(|tags| tags.0),
+                metadata: metadata.0,
+            }),
+            JsonElement::Other => return None,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonBounds {
+    minlat: f64,
+    minlon: f64,
+    maxlat: f64,
+    maxlon: f64,
+}
+
+/// OSM JSON document, the other fields Overpass writes are ignored
+#[derive(Deserialize)]
+struct JsonOsm {
+    generator: Option<String>,
+    bounds: Option<JsonBounds>,
+    elements: Vec<JsonElement>,
+}
+
+/// reads an OSM JSON document, as written by the OSM API or by Overpass, into memory. Overpass
+/// lists the elements in the order of the query, they are grouped nodes, ways and relations as
+/// in OSM XML files
+pub fn read_json<R: Read>(reader: R) -> Result<Osm> {
+    let document: JsonOsm = serde_json::from_reader(reader).map_err(io::Error::from)?;
+    let mut osm = Osm {
+        version: Some(OSM_VERSION.to_string()),
+        generator: document.generator,
+        bounds: document.bounds.map(|bounds| Bounds {
+            minlat: bounds.minlat,
+            minlon: bounds.minlon,
+            maxlat: bounds.maxlat,
+            maxlon: bounds.maxlon,
+        }),
+        ..Default::default()
+    };
+    let mut skipped = 0;
+    for element in document.elements {
+        match element.into_element() {
+            Some(element) => osm.push(element),
+            None => skipped += 1,
+        }
+    }
+    if skipped > 0 {
+        debug!(
+            "skipped {} elements that are not nodes, ways or relations",
+            skipped
+        );
+    }
+    Ok(osm)
+}
+
+/// incrementally writes elements as OSM JSON, the format of the OSM API and of Overpass: an
+/// object with the version, generator and bounds and an `elements` array
+pub struct JsonWriter<W: Write> {
+    writer: W,
+    empty: bool,
+}
+
+impl<W: Write> JsonWriter<W> {
+    pub fn new(mut writer: W, bounds: Option<&Bounds>) -> io::Result<Self> {
+        write!(
+            writer,
+            "{{\"version\":{},\"generator\":\"{}\",",
+            OSM_VERSION, GENERATOR
+        )?;
+        if let Some(bounds) = bounds {
+            let bounds = JsonBounds {
+                minlat: bounds.minlat,
+                minlon: bounds.minlon,
+                maxlat: bounds.maxlat,
+                maxlon: bounds.maxlon,
+            };
+            writer.write_all(b"\"bounds\":")?;
+            serde_json::to_writer(&mut writer, &bounds)?;
+            writer.write_all(b",")?;
+        }
+        writer.write_all(b"\"elements\":[")?;
+        Ok(Self {
+            writer,
+            empty: true,
+        })
+    }
+
+    pub fn write(&mut self, element: &Element) -> io::Result<()> {
+        self.writer
+            .write_all(if self.empty { b"\n" } else { b",\n" })?;
+        self.empty = false;
+        serde_json::to_writer(&mut self.writer, &JsonElement::from(element))?;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.write_all(b"\n]}\n")?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::{json, Value};
+
+    use crate::{
+        stream::{ElementReader, MemoryReader, XmlReader},
+        Element, MemberType, Tagged,
+    };
+
+    use super::{read_json, JsonWriter};
+
+    const OSM_XML: &str = r#"<osm version="0.6" generator="test">
+  <bounds minlat="47.0" minlon="28.8" maxlat="47.1" maxlon="28.9"/>
+  <node id="1" lat="47.0" lon="28.8" version="3" user="mapper"/>
+  <node id="2" lat="47.1" lon="28.8"><tag k="name" v="Fântână"/><tag k="amenity" v="drinking_water"/></node>
+  <way id="10"><nd ref="1"/><nd ref="2"/><tag k="highway" v="footway"/></way>
+  <relation id="100"><member type="way" ref="10" role="outer"/></relation>
+</osm>"#;
+
+    #[test]
+    fn write_osm_json() {
+        let mut reader = XmlReader::new(OSM_XML.as_bytes());
+        let elements = reader
+            .by_ref()
+            .collect::<std::io::Result<Vec<Element>>>()
+            .unwrap();
+        let mut writer = JsonWriter::new(Vec::new(), reader.bounds()).unwrap();
+        elements
+            .iter()
+            .for_each(|element| writer.write(element).unwrap());
+        let json: Value = serde_json::from_slice(&writer.finish().unwrap()).unwrap();
+
+        assert_eq!(json["version"], json!(0.6));
+        assert_eq!(json["bounds"]["maxlon"], json!(28.9));
+        assert_eq!(
+            json["elements"],
+            json!([
+                {"type": "node", "id": 1, "lat": 47.0, "lon": 28.8, "version": 3, "user": "mapper"},
+                {"type": "node", "id": 2, "lat": 47.1, "lon": 28.8,
+                    "tags": {"name": "Fântână", "amenity": "drinking_water"}},
+                {"type": "way", "id": 10, "nodes": [1, 2], "tags": {"highway": "footway"}},
+                {"type": "relation", "id": 100,
+                    "members": [{"type": "way", "ref": 10, "role": "outer"}]},
+            ])
+        );
+    }
+
+    const OVERPASS_JSON: &str = r#"{
+  "version": 0.6,
+  "generator": "Overpass API 0.7.62",
+  "osm3s": {"timestamp_osm_base": "2024-05-01T00:00:00Z"},
+  "elements": [
+    {"type": "way", "id": 10, "nodes": [1, 2, 1], "tags": {"leisure": "park", "name": "Parcul Alunelul"}},
+    {"type": "node", "id": 1, "lat": 47.0, "lon": 28.8, "version": 2, "uid": 7},
+    {"type": "relation", "id": 100, "members": [{"type": "way", "ref": 10, "role": "outer", "geometry": []}], "tags": {"type": "multipolygon"}},
+    {"type": "node", "id": 2, "lat": 47.1, "lon": 28.8},
+    {"type": "area", "id": 3600000100, "tags": {"name": "Chișinău"}}
+  ]
+}"#;
+
+    #[test]
+    fn read_overpass_json() {
+        let osm = read_json(OVERPASS_JSON.as_bytes()).unwrap();
+        assert_eq!(osm.generator.as_deref(), Some("Overpass API 0.7.62"));
+        assert_eq!(osm.node.len(), 2);
+        assert_eq!(osm.node[0].metadata.version, Some(2));
+        assert_eq!(osm.node[0].metadata.uid, Some(7));
+        assert_eq!(osm.way[0].nd.len(), 3);
+        assert_eq!(osm.way[0].tag("name").as_deref(), Some("Parcul Alunelul"));
+        assert_eq!(osm.relation[0].member[0].member_type, MemberType::Way);
+        assert_eq!(osm.relation[0].member[0].role, "outer");
+
+        // the elements are grouped by type and written back in that order
+        let mut writer = JsonWriter::new(Vec::new(), None).unwrap();
+        MemoryReader::from(osm).for_each(|element| writer.write(&element.unwrap()).unwrap());
+        let json: Value = serde_json::from_slice(&writer.finish().unwrap()).unwrap();
+        let types: Vec<&str> = json["elements"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|element| element["type"].as_str().unwrap())
+            .collect();
+        assert_eq!(types, vec!["node", "node", "way", "relation"]);
+
+        assert!(read_json(r#"{"elements": [{"type": "node", "id": 1}]}"#.as_bytes()).is_err());
+    }
+}
@@ -1,19 +1,49 @@
+pub mod binary;
+pub mod classes;
+pub mod compression;
+pub mod dataset;
+pub mod error;
+pub mod extract;
+pub mod filter;
+pub mod geojson;
+pub mod geometry;
+pub mod input;
+pub mod json;
+pub mod mapped;
+pub mod merge;
+pub mod osc;
+pub mod pbf;
+pub mod spatial;
+pub mod stream;
+pub mod symbol;
+pub mod tags;
 pub mod utils;
+pub mod validate;
+
+pub use classes::{Class, Classes};
+pub use error::{Error, Result};
+pub use filter::Filter;
+pub use symbol::Symbol;
+pub use tags::Tagged;
 
 use std::{
     collections::{HashMap, HashSet},
+    fmt,
+    str::FromStr,
     sync::Arc,
 };
 
 use serde::{Deserialize, Serialize};
 
 pub const TILE_SIZE: u32 = 256;
+pub const OSM_VERSION: &str = "0.6";
+pub const GENERATOR: &str = concat!("osm-tiles ", env!("CARGO_PKG_VERSION"));
 
 pub type RelationToTile = HashMap<i32, HashMap<i32, HashSet<u64>>>;
 pub type WayToTile = HashMap<i32, HashMap<i32, HashSet<u64>>>;
 pub type NodeToTile = HashMap<u64, (f64, f64)>;
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Default, Clone)]
 pub struct Node {
     #[serde(rename = "@id")]
     pub id: u64,
@@ -21,35 +51,88 @@ pub struct Node {
     pub lat: f64,
     #[serde(rename = "@lon")]
     pub lon: f64,
+    #[serde(flatten)]
+    pub metadata: Metadata,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tag: Option<Vec<Tag>>,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Clone)]
 pub struct Nd {
     #[serde(rename = "@ref")]
     pub reference: u64,
 }
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Clone)]
 pub struct Tag {
     #[serde(rename = "@k")]
-    pub k: String,
+    pub k: Symbol,
     #[serde(rename = "@v")]
-    pub v: String,
+    pub v: Symbol,
 }
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Default, Clone)]
 pub struct Way {
     #[serde(rename = "@id")]
     pub id: u64,
+    #[serde(flatten)]
+    pub metadata: Metadata,
     pub nd: Vec<Nd>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tag: Option<Vec<Tag>>,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum MemberType {
+    Node,
+    Way,
+    Relation,
+}
+
+impl FromStr for MemberType {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "node" => Ok(MemberType::Node),
+            "way" => Ok(MemberType::Way),
+            "relation" => Ok(MemberType::Relation),
+            _ => Err(format!("unknown member type {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for MemberType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MemberType::Node => write!(f, "node"),
+            MemberType::Way => write!(f, "way"),
+            MemberType::Relation => write!(f, "relation"),
+        }
+    }
+}
+
+/// id qualified with the type of the element, OSM ids are only unique within one element type
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub enum ElementId {
+    Node(u64),
+    Way(u64),
+    Relation(u64),
+}
+
+impl fmt::Display for ElementId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ElementId::Node(id) => write!(f, "node {}", id),
+            ElementId::Way(id) => write!(f, "way {}", id),
+            ElementId::Relation(id) => write!(f, "relation {}", id),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone)]
 pub struct Member {
     #[serde(rename = "@type")]
-    pub member_type: String,
+    pub member_type: MemberType,
     #[serde(rename = "@ref")]
     pub member_ref: u64,
     #[serde(rename = "@role")]
@@ -58,50 +141,163 @@ pub struct Member {
     pub tag: Option<Vec<Tag>>,
 }
 
-#[derive(Deserialize, Serialize)]
+impl Member {
+    pub fn element_id(&self) -> ElementId {
+        match self.member_type {
+            MemberType::Node => ElementId::Node(self.member_ref),
+            MemberType::Way => ElementId::Way(self.member_ref),
+            MemberType::Relation => ElementId::Relation(self.member_ref),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Default, Clone)]
 pub struct Relation {
     #[serde(rename = "@id")]
     pub id: u64,
+    #[serde(flatten)]
+    pub metadata: Metadata,
     pub member: Vec<Member>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tag: Option<Vec<Tag>>,
 }
 
-#[derive(Deserialize, Serialize)]
+/// optional version, timestamp, changeset, user, uid and visible attributes of nodes, ways and
+/// relations, shared by the readers and writers of every format. Flattened into the elements as
+/// attributes next to the id, OSM JSON names them without the `@`
+#[derive(Deserialize, Serialize, Default, Clone, PartialEq, Debug)]
+pub struct Metadata {
+    #[serde(
+        rename = "@version",
+        alias = "version",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub version: Option<u32>,
+    #[serde(
+        rename = "@timestamp",
+        alias = "timestamp",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub timestamp: Option<String>,
+    #[serde(
+        rename = "@changeset",
+        alias = "changeset",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub changeset: Option<u64>,
+    #[serde(
+        rename = "@user",
+        alias = "user",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub user: Option<String>,
+    #[serde(
+        rename = "@uid",
+        alias = "uid",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub uid: Option<u64>,
+    #[serde(
+        rename = "@visible",
+        alias = "visible",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub visible: Option<bool>,
+}
+
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub struct Bounds {
+    #[serde(rename = "@minlat")]
+    pub minlat: f64,
+    #[serde(rename = "@minlon")]
+    pub minlon: f64,
+    #[serde(rename = "@maxlat")]
+    pub maxlat: f64,
+    #[serde(rename = "@maxlon")]
+    pub maxlon: f64,
+}
+
+#[derive(Deserialize, Serialize, Default)]
 pub struct Osm {
+    #[serde(rename = "@version", skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(rename = "@generator", skip_serializing_if = "Option::is_none")]
+    pub generator: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bounds: Option<Bounds>,
+    #[serde(default)]
     pub relation: Vec<Arc<Relation>>,
+    #[serde(default)]
     pub way: Vec<Arc<Way>>,
+    #[serde(default)]
     pub node: Vec<Arc<Node>>,
 }
-#[derive(PartialEq, Eq, Hash, Clone)]
-pub enum Type {
-    Park,
-    Forest,
-    Building,
-    Generic,
-    Water,
-    WaterRiver,
+
+/// single element of an OSM file, used when processing files one element at a time
+pub enum Element {
+    Node(Node),
+    Way(Way),
+    Relation(Relation),
 }
 
-pub struct LoopWithType {
-    pub member_type: Type,
-    pub memeber_loop: Vec<u64>,
-    pub way_id: Option<u64>,
+impl Osm {
+    pub fn push(&mut self, element: Element) {
+        match element {
+            Element::Node(node) => self.node.push(Arc::new(node)),
+            Element::Way(way) => self.way.push(Arc::new(way)),
+            Element::Relation(relation) => self.relation.push(Arc::new(relation)),
+        }
+    }
+
+    /// nodes, ways and relations of the dataset, elements still shared elsewhere are cloned
+    pub fn into_elements(self) -> impl Iterator<Item = Element> {
+        let nodes = self
+            .node
+            .into_iter()
+            .map(|node| Element::Node(Arc::unwrap_or_clone(node)));
+        let ways = self
+            .way
+            .into_iter()
+            .map(|way| Element::Way(Arc::unwrap_or_clone(way)));
+        let relations = self
+            .relation
+            .into_iter()
+            .map(|relation| Element::Relation(Arc::unwrap_or_clone(relation)));
+        nodes.chain(ways).chain(relations)
+    }
 }
 
-impl LoopWithType {
-    pub fn new_no_default() -> Self {
-        Self {
-            member_type: Type::Generic,
-            memeber_loop: Vec::<u64>::new(),
-            way_id: None,
+impl Element {
+    pub fn id(&self) -> u64 {
+        match self {
+            Element::Node(node) => node.id,
+            Element::Way(way) => way.id,
+            Element::Relation(relation) => relation.id,
+        }
+    }
+
+    pub fn element_id(&self) -> ElementId {
+        match self {
+            Element::Node(node) => ElementId::Node(node.id),
+            Element::Way(way) => ElementId::Way(way.id),
+            Element::Relation(relation) => ElementId::Relation(relation.id),
         }
     }
-    pub fn new_with_type(way_id: u64, memeber_type: Type) -> Self {
-        Self {
-            member_type: memeber_type,
-            memeber_loop: Vec::<u64>::new(),
-            way_id: Some(way_id),
+
+    pub fn tag_mut(&mut self) -> &mut Option<Vec<Tag>> {
+        match self {
+            Element::Node(node) => &mut node.tag,
+            Element::Way(way) => &mut way.tag,
+            Element::Relation(relation) => &mut relation.tag,
         }
     }
 }
+
+pub struct LoopWithType {
+    pub memeber_loop: Vec<u64>,
+    pub way_id: Option<u64>,
+    /// formed by members with the `inner` role, a hole in the area
+    pub inner: bool,
+    /// the last node is the first one
+    pub closed: bool,
+}
@@ -0,0 +1,837 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, Write},
+    ops::Range,
+    path::Path,
+    sync::{Arc, OnceLock},
+};
+
+use memmap2::Mmap;
+
+use crate::{
+    binary::Header, compression::path_error, dataset::Dataset, Element, Error, Member, MemberType,
+    Nd, Node, Osm, Relation, Result, Symbol, Tag, Way,
+};
+
+/// first bytes of a memory-mapped dataset, followed by the format version and the number of
+/// sections as little endian u32 and the offset and length of every section as little endian u64.
+/// Sections are 8 byte aligned arrays of little endian numbers, element ids are sorted so that
+/// lookups are binary searches directly on the mapped file
+pub const MAGIC: &[u8; 8] = b"OSMTMAP\0";
+pub const FORMAT_VERSION: u32 = 1;
+
+const HEADER: usize = 0;
+/// u64 offsets into `STRING_BYTES`, one more than there are strings
+const STRING_OFFSETS: usize = 1;
+const STRING_BYTES: usize = 2;
+const NODE_IDS: usize = 3;
+/// f64 latitude and longitude pairs
+const NODE_COORDINATES: usize = 4;
+const NODE_TAG_OFFSETS: usize = 5;
+/// u32 key and value string indexes
+const NODE_TAGS: usize = 6;
+const WAY_IDS: usize = 7;
+const WAY_NODE_OFFSETS: usize = 8;
+const WAY_NODES: usize = 9;
+const WAY_TAG_OFFSETS: usize = 10;
+const WAY_TAGS: usize = 11;
+const RELATION_IDS: usize = 12;
+const RELATION_MEMBER_OFFSETS: usize = 13;
+const RELATION_MEMBER_REFS: usize = 14;
+/// one byte per member, 0 node, 1 way and 2 relation as in PBF
+const RELATION_MEMBER_TYPES: usize = 15;
+/// u32 string index of the role of every member
+const RELATION_MEMBER_ROLES: usize = 16;
+const RELATION_TAG_OFFSETS: usize = 17;
+const RELATION_TAGS: usize = 18;
+const SECTIONS: usize = 19;
+
+/// bytes of the item at the index of a section of `size` byte items, `None` past its end
+fn item(bytes: &[u8], index: usize, size: usize) -> Option<&[u8]> {
+    let start = index.checked_mul(size)?;
+    bytes.get(start..start.checked_add(size)?)
+}
+
+fn u32_at(bytes: &[u8], index: usize) -> Option<u32> {
+    item(bytes, index, 4).map(|item| u32::from_le_bytes(item.try_into().unwrap()))
+}
+
+fn u64_at(bytes: &[u8], index: usize) -> Option<u64> {
+    item(bytes, index, 8).map(|item| u64::from_le_bytes(item.try_into().unwrap()))
+}
+
+fn f64_at(bytes: &[u8], index: usize) -> Option<f64> {
+    item(bytes, index, 8).map(|item| f64::from_le_bytes(item.try_into().unwrap()))
+}
+
+/// items between two offsets of a section of `size` byte items
+fn items(bytes: &[u8], range: Range<usize>, size: usize) -> Option<&[u8]> {
+    bytes.get(range.start.checked_mul(size)?..range.end.checked_mul(size)?)
+}
+
+/// index of the id in a sorted u64 section
+fn find(ids: &[u8], id: u64) -> Option<usize> {
+    let (mut low, mut high) = (0, ids.len() / 8);
+    while low < high {
+        let middle = (low + high) / 2;
+        match u64_at(ids, middle)?.cmp(&id) {
+            std::cmp::Ordering::Less => low = middle + 1,
+            std::cmp::Ordering::Greater => high = middle,
+            std::cmp::Ordering::Equal => return Some(middle),
+        }
+    }
+    None
+}
+
+/// dataset queried in place from a memory-mapped file, nothing is deserialized up front. Only the
+/// section lengths are checked when the file is opened, every lookup checks the offsets it reads
+/// and treats elements pointing outside of their sections as missing. Element metadata is not
+/// kept in this layout
+pub struct MappedDataset {
+    map: Mmap,
+    sections: Vec<(usize, usize)>,
+    header: Header,
+    /// symbols of the strings of the file, interned the first time a tag is converted to a `Tag`
+    symbols: OnceLock<Box<[OnceLock<Symbol>]>>,
+}
+
+/// way read in place, its nodes and tags are borrowed from the mapped file
+#[derive(Clone, Copy)]
+pub struct MappedWay<'a> {
+    pub id: u64,
+    nodes: &'a [u8],
+    tags: &'a [u8],
+    dataset: &'a MappedDataset,
+}
+
+impl<'a> MappedWay<'a> {
+    pub fn nodes(&self) -> impl Iterator<Item = u64> + 'a {
+        self.nodes
+            .chunks_exact(8)
+            .map(|node| u64::from_le_bytes(node.try_into().unwrap()))
+    }
+
+    pub fn tags(&self) -> impl Iterator<Item = (&'a str, &'a str)> + 'a {
+        self.dataset.tags(self.tags)
+    }
+}
+
+/// relation read in place, its members and tags are borrowed from the mapped file
+#[derive(Clone, Copy)]
+pub struct MappedRelation<'a> {
+    pub id: u64,
+    refs: &'a [u8],
+    types: &'a [u8],
+    roles: &'a [u8],
+    tags: &'a [u8],
+    dataset: &'a MappedDataset,
+}
+
+impl<'a> MappedRelation<'a> {
+    /// type, reference and role of every member
+    pub fn members(&self) -> impl Iterator<Item = (MemberType, u64, &'a str)> + 'a {
+        let dataset = self.dataset;
+        self.refs
+            .chunks_exact(8)
+            .zip(self.types)
+            .zip(self.roles.chunks_exact(4))
+            .map(move |((member_ref, member_type), role)| {
+                (
+                    match member_type {
+                        0 => MemberType::Node,
+                        1 => MemberType::Way,
+                        _ => MemberType::Relation,
+                    },
+                    u64::from_le_bytes(member_ref.try_into().unwrap()),
+                    dataset.string(u32::from_le_bytes(role.try_into().unwrap())),
+                )
+            })
+    }
+
+    pub fn tags(&self) -> impl Iterator<Item = (&'a str, &'a str)> + 'a {
+        self.dataset.tags(self.tags)
+    }
+}
+
+impl MappedDataset {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|error| path_error(path, error))?;
+        // the file is only read, it must not be truncated while it is mapped
+        let map = unsafe { Mmap::map(&file)? };
+
+        if !map.starts_with(MAGIC) || map.len() < 16 {
+            return Err(Error::Corrupt(
+                "not a memory-mapped osm-tiles dataset".to_string(),
+            ));
+        }
+        let version = u32_at(&map[8..], 0).unwrap_or_default();
+        if version != FORMAT_VERSION {
+            return Err(Error::UnsupportedVersion {
+                found: version,
+                expected: FORMAT_VERSION,
+            });
+        }
+        let count = u32_at(&map[12..], 0).unwrap_or_default() as usize;
+        if count != SECTIONS || map.len() < 16 + count * 16 {
+            return Err(Error::Corrupt(format!(
+                "unexpected number of sections {}",
+                count
+            )));
+        }
+        let sections = (0..count)
+            .map(|section| {
+                let offset = u64_at(&map[16..], section * 2).unwrap_or(u64::MAX);
+                let length = u64_at(&map[16..], section * 2 + 1).unwrap_or(u64::MAX);
+                match (usize::try_from(offset), usize::try_from(length)) {
+                    (Ok(offset), Ok(length))
+                        if offset
+                            .checked_add(length)
+                            .is_some_and(|end| end <= map.len()) =>
+                    {
+                        Ok((offset, length))
+                    }
+                    _ => Err(Error::Corrupt(format!("section {} out of bounds", section))),
+                }
+            })
+            .collect::<Result<Vec<(usize, usize)>>>()?;
+
+        let (offset, length) = sections[HEADER];
+        let header = ciborium::from_reader(&map[offset..offset + length])
+            .map_err(|error| Error::Corrupt(format!("invalid dataset header: {}", error)))?;
+
+        let dataset = MappedDataset {
+            map,
+            sections,
+            header,
+            symbols: OnceLock::new(),
+        };
+        dataset.check_sections()?;
+        Ok(dataset)
+    }
+
+    fn section(&self, section: usize) -> &[u8] {
+        let (offset, length) = self.sections[section];
+        &self.map[offset..offset + length]
+    }
+
+    /// checks the lengths of the sections and the last offset of every offset section, the
+    /// offsets in between are checked by the lookups reading them
+    fn check_sections(&self) -> Result<()> {
+        let check = |ids: usize, offsets: usize, items: usize, item_size: usize| {
+            let count = self.section(ids).len() / 8;
+            let offsets = self.section(offsets);
+            let valid = offsets.len() == (count + 1) * 8
+                && u64_at(offsets, count)
+                    .and_then(|end| usize::try_from(end).ok())
+                    .and_then(|end| end.checked_mul(item_size))
+                    .is_some_and(|end| end <= self.section(items).len());
+            if valid {
+                Ok(())
+            } else {
+                Err(Error::Corrupt(format!("section {} is inconsistent", items)))
+            }
+        };
+        check(NODE_IDS, NODE_TAG_OFFSETS, NODE_TAGS, 8)?;
+        check(WAY_IDS, WAY_NODE_OFFSETS, WAY_NODES, 8)?;
+        check(WAY_IDS, WAY_TAG_OFFSETS, WAY_TAGS, 8)?;
+        check(
+            RELATION_IDS,
+            RELATION_MEMBER_OFFSETS,
+            RELATION_MEMBER_REFS,
+            8,
+        )?;
+        check(
+            RELATION_IDS,
+            RELATION_MEMBER_OFFSETS,
+            RELATION_MEMBER_TYPES,
+            1,
+        )?;
+        check(
+            RELATION_IDS,
+            RELATION_MEMBER_OFFSETS,
+            RELATION_MEMBER_ROLES,
+            4,
+        )?;
+        check(RELATION_IDS, RELATION_TAG_OFFSETS, RELATION_TAGS, 8)?;
+
+        let string_offsets = self.section(STRING_OFFSETS);
+        let strings_end = (string_offsets.len() / 8)
+            .checked_sub(1)
+            .and_then(|last| u64_at(string_offsets, last));
+        if strings_end
+            .and_then(|end| usize::try_from(end).ok())
+            .is_none_or(|end| end > self.section(STRING_BYTES).len())
+            || self.section(NODE_COORDINATES).len() != self.section(NODE_IDS).len() * 2
+        {
+            return Err(Error::Corrupt(
+                "string or node section is inconsistent".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    fn string_count(&self) -> usize {
+        (self.section(STRING_OFFSETS).len() / 8).saturating_sub(1)
+    }
+
+    /// empty for an index or offsets outside of the string sections
+    fn string(&self, index: u32) -> &str {
+        let offsets = self.section(STRING_OFFSETS);
+        let bytes = u64_at(offsets, index as usize)
+            .zip(u64_at(offsets, index as usize + 1))
+            .and_then(|(start, end)| {
+                let start = usize::try_from(start).ok()?;
+                self.section(STRING_BYTES)
+                    .get(start..usize::try_from(end).ok()?)
+            });
+        bytes
+            .and_then(|bytes| std::str::from_utf8(bytes).ok())
+            .unwrap_or_default()
+    }
+
+    fn symbol(&self, index: u32) -> Symbol {
+        let symbols = self
+            .symbols
+            .get_or_init(|| (0..self.string_count()).map(|_| OnceLock::new()).collect());
+        match symbols.get(index as usize) {
+            Some(symbol) => *symbol.get_or_init(|| Symbol::new(self.string(index))),
+            None => Symbol::new(""),
+        }
+    }
+
+    fn tags<'a>(&'a self, tags: &'a [u8]) -> impl Iterator<Item = (&'a str, &'a str)> + 'a {
+        tags.chunks_exact(8).map(|tag| {
+            (
+                self.string(u32::from_le_bytes(tag[..4].try_into().unwrap())),
+                self.string(u32::from_le_bytes(tag[4..].try_into().unwrap())),
+            )
+        })
+    }
+
+    fn symbol_tags(&self, tags: &[u8]) -> Option<Vec<Tag>> {
+        if tags.is_empty() {
+            return None;
+        }
+        Some(
+            tags.chunks_exact(8)
+                .map(|tag| Tag {
+                    k: self.symbol(u32::from_le_bytes(tag[..4].try_into().unwrap())),
+                    v: self.symbol(u32::from_le_bytes(tag[4..].try_into().unwrap())),
+                })
+                .collect(),
+        )
+    }
+
+    /// items of the element at the index, `None` when its offsets are outside of the sections
+    fn element_items(
+        &self,
+        offsets: usize,
+        section: usize,
+        size: usize,
+        index: usize,
+    ) -> Option<&[u8]> {
+        let offsets = self.section(offsets);
+        let start = usize::try_from(u64_at(offsets, index)?).ok()?;
+        let end = usize::try_from(u64_at(offsets, index + 1)?).ok()?;
+        items(self.section(section), start..end, size)
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.section(NODE_IDS).len() / 8
+    }
+
+    pub fn way_count(&self) -> usize {
+        self.section(WAY_IDS).len() / 8
+    }
+
+    pub fn relation_count(&self) -> usize {
+        self.section(RELATION_IDS).len() / 8
+    }
+
+    fn node_at(&self, index: usize) -> Option<Node> {
+        let coordinates = self.section(NODE_COORDINATES);
+        Some(Node {
+            id: u64_at(self.section(NODE_IDS), index)?,
+            lat: f64_at(coordinates, index * 2)?,
+            lon: f64_at(coordinates, index * 2 + 1)?,
+            tag: self.symbol_tags(self.element_items(NODE_TAG_OFFSETS, NODE_TAGS, 8, index)?),
+            ..Default::default()
+        })
+    }
+
+    fn way_at(&self, index: usize) -> Option<MappedWay<'_>> {
+        Some(MappedWay {
+            id: u64_at(self.section(WAY_IDS), index)?,
+            nodes: self.element_items(WAY_NODE_OFFSETS, WAY_NODES, 8, index)?,
+            tags: self.element_items(WAY_TAG_OFFSETS, WAY_TAGS, 8, index)?,
+            dataset: self,
+        })
+    }
+
+    fn relation_at(&self, index: usize) -> Option<MappedRelation<'_>> {
+        Some(MappedRelation {
+            id: u64_at(self.section(RELATION_IDS), index)?,
+            refs: self.element_items(RELATION_MEMBER_OFFSETS, RELATION_MEMBER_REFS, 8, index)?,
+            types: self.element_items(RELATION_MEMBER_OFFSETS, RELATION_MEMBER_TYPES, 1, index)?,
+            roles: self.element_items(RELATION_MEMBER_OFFSETS, RELATION_MEMBER_ROLES, 4, index)?,
+            tags: self.element_items(RELATION_TAG_OFFSETS, RELATION_TAGS, 8, index)?,
+            dataset: self,
+        })
+    }
+
+    pub fn mapped_way(&self, id: u64) -> Option<MappedWay<'_>> {
+        self.way_at(find(self.section(WAY_IDS), id)?)
+    }
+
+    pub fn mapped_relation(&self, id: u64) -> Option<MappedRelation<'_>> {
+        self.relation_at(find(self.section(RELATION_IDS), id)?)
+    }
+
+    /// all the relations in id order, read in place
+    pub fn mapped_relations(&self) -> impl Iterator<Item = MappedRelation<'_>> {
+        (0..self.relation_count()).filter_map(|index| self.relation_at(index))
+    }
+
+    fn to_way(&self, way: MappedWay) -> Way {
+        Way {
+            id: way.id,
+            nd: way.nodes().map(|reference| Nd { reference }).collect(),
+            tag: self.symbol_tags(way.tags),
+            ..Default::default()
+        }
+    }
+
+    fn to_relation(&self, relation: MappedRelation) -> Relation {
+        Relation {
+            id: relation.id,
+            member: relation
+                .members()
+                .map(|(member_type, member_ref, role)| Member {
+                    member_type,
+                    member_ref,
+                    role: role.to_string(),
+                    tag: None,
+                })
+                .collect(),
+            tag: self.symbol_tags(relation.tags),
+            ..Default::default()
+        }
+    }
+
+    /// deserializes the whole dataset
+    pub fn to_osm(&self) -> Osm {
+        Osm {
+            bounds: self.header.bounds.clone(),
+            node: (0..self.node_count())
+                .filter_map(|index| self.node_at(index))
+                .map(Arc::new)
+                .collect(),
+            way: (0..self.way_count())
+                .filter_map(|index| self.way_at(index))
+                .map(|way| Arc::new(self.to_way(way)))
+                .collect(),
+            relation: self
+                .mapped_relations()
+                .map(|relation| Arc::new(self.to_relation(relation)))
+                .collect(),
+            ..Default::default()
+        }
+    }
+}
+
+impl Dataset for MappedDataset {
+    fn node(&self, id: u64) -> Option<(f64, f64)> {
+        let index = find(self.section(NODE_IDS), id)?;
+        let coordinates = self.section(NODE_COORDINATES);
+        Some((
+            f64_at(coordinates, index * 2)?,
+            f64_at(coordinates, index * 2 + 1)?,
+        ))
+    }
+
+    fn tagged_nodes(&self) -> Box<dyn Iterator<Item = (u64, (f64, f64))> + '_> {
+        let (ids, coordinates) = (self.section(NODE_IDS), self.section(NODE_COORDINATES));
+        Box::new(
+            (0..self.node_count())
+                .filter(|&index| {
+                    self.element_items(NODE_TAG_OFFSETS, NODE_TAGS, 8, index)
+                        .is_some_and(|tags| !tags.is_empty())
+                })
+                .filter_map(|index| {
+                    Some((
+                        u64_at(ids, index)?,
+                        (
+                            f64_at(coordinates, index * 2)?,
+                            f64_at(coordinates, index * 2 + 1)?,
+                        ),
+                    ))
+                }),
+        )
+    }
+
+    fn way(&self, id: u64) -> Option<Arc<Way>> {
+        self.mapped_way(id).map(|way| Arc::new(self.to_way(way)))
+    }
+
+    fn ways(&self) -> Box<dyn Iterator<Item = Arc<Way>> + '_> {
+        Box::new(
+            (0..self.way_count())
+                .filter_map(|index| self.way_at(index))
+                .map(|way| Arc::new(self.to_way(way))),
+        )
+    }
+
+    fn way_nodes(&self) -> Box<dyn Iterator<Item = (u64, Vec<u64>)> + '_> {
+        Box::new(
+            (0..self.way_count())
+                .filter_map(|index| self.way_at(index))
+                .map(|way| (way.id, way.nodes().collect())),
+        )
+    }
+
+    fn relations(&self) -> Vec<Arc<Relation>> {
+        self.mapped_relations()
+            .map(|relation| Arc::new(self.to_relation(relation)))
+            .collect()
+    }
+}
+
+#[derive(Default)]
+struct Strings {
+    index: HashMap<String, u32>,
+    offsets: Vec<u64>,
+    bytes: Vec<u8>,
+}
+
+impl Strings {
+    fn get(&mut self, value: &str) -> u32 {
+        if let Some(index) = self.index.get(value) {
+            return *index;
+        }
+        let index = self.offsets.len() as u32;
+        self.offsets.push(self.bytes.len() as u64);
+        self.bytes.extend_from_slice(value.as_bytes());
+        self.index.insert(value.to_string(), index);
+        index
+    }
+}
+
+/// elements of one type, items are the node references of ways or the members of relations
+#[derive(Default)]
+struct Table {
+    ids: Vec<u64>,
+    item_offsets: Vec<u64>,
+    items: Vec<u64>,
+    tag_offsets: Vec<u64>,
+    tags: Vec<u32>,
+}
+
+impl Table {
+    fn push(
+        &mut self,
+        id: u64,
+        items: impl Iterator<Item = u64>,
+        tags: &Option<Vec<Tag>>,
+        strings: &mut Strings,
+    ) {
+        self.ids.push(id);
+        self.item_offsets.push(self.items.len() as u64);
+        self.items.extend(items);
+        self.tag_offsets.push(self.tags.len() as u64 / 2);
+        tags.iter().flatten().for_each(|tag| {
+            self.tags.push(strings.get(&tag.k));
+            self.tags.push(strings.get(&tag.v));
+        });
+    }
+
+    /// element indexes ordered by id, inputs are usually sorted already
+    fn order(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.ids.len()).collect();
+        if !self.ids.windows(2).all(|pair| pair[0] <= pair[1]) {
+            order.sort_by_key(|&index| self.ids[index]);
+        }
+        order
+    }
+
+    fn range(offsets: &[u64], end: usize, index: usize) -> std::ops::Range<usize> {
+        offsets[index] as usize
+            ..offsets
+                .get(index + 1)
+                .map_or(end, |&offset| offset as usize)
+    }
+
+    fn item_range(&self, index: usize) -> std::ops::Range<usize> {
+        Table::range(&self.item_offsets, self.items.len(), index)
+    }
+
+    fn tag_range(&self, index: usize) -> std::ops::Range<usize> {
+        Table::range(&self.tag_offsets, self.tags.len() / 2, index)
+    }
+
+    /// id, item offset, item, tag offset and tag sections in id order
+    fn sections(&self, order: &[usize]) -> [Vec<u8>; 5] {
+        let mut item_offsets = vec![0u64];
+        let mut tag_offsets = vec![0u64];
+        let mut items = Vec::new();
+        let mut tags = Vec::new();
+        order.iter().for_each(|&index| {
+            items.extend(self.item_range(index).map(|item| self.items[item]));
+            item_offsets.push(items.len() as u64);
+            let range = self.tag_range(index);
+            tags.extend_from_slice(&self.tags[range.start * 2..range.end * 2]);
+            tag_offsets.push(tags.len() as u64 / 2);
+        });
+        [
+            u64s(order.iter().map(|&index| self.ids[index])),
+            u64s(item_offsets.into_iter()),
+            u64s(items.into_iter()),
+            u64s(tag_offsets.into_iter()),
+            tags.iter().flat_map(|tag| tag.to_le_bytes()).collect(),
+        ]
+    }
+}
+
+fn u64s(values: impl Iterator<Item = u64>) -> Vec<u8> {
+    values.flat_map(|value| value.to_le_bytes()).collect()
+}
+
+/// collects the elements into the sorted arrays of the memory-mapped layout, the arrays are kept
+/// in memory until `finish`
+#[derive(Default)]
+pub struct MappedWriter {
+    strings: Strings,
+    nodes: Table,
+    coordinates: Vec<f64>,
+    ways: Table,
+    relations: Table,
+    member_types: Vec<u8>,
+    member_roles: Vec<u32>,
+}
+
+impl MappedWriter {
+    pub fn new() -> Self {
+        MappedWriter::default()
+    }
+
+    pub fn write(&mut self, element: &Element) {
+        match element {
+            Element::Node(node) => {
+                self.nodes
+                    .push(node.id, std::iter::empty(), &node.tag, &mut self.strings);
+                self.coordinates.push(node.lat);
+                self.coordinates.push(node.lon);
+            }
+            Element::Way(way) => {
+                self.ways.push(
+                    way.id,
+                    way.nd.iter().map(|nd| nd.reference),
+                    &way.tag,
+                    &mut self.strings,
+                );
+            }
+            Element::Relation(relation) => {
+                self.relations.push(
+                    relation.id,
+                    relation.member.iter().map(|member| member.member_ref),
+                    &relation.tag,
+                    &mut self.strings,
+                );
+                relation.member.iter().for_each(|member| {
+                    self.member_types.push(match member.member_type {
+                        MemberType::Node => 0,
+                        MemberType::Way => 1,
+                        MemberType::Relation => 2,
+                    });
+                    self.member_roles.push(self.strings.get(&member.role));
+                });
+            }
+        }
+    }
+
+    pub fn finish<W: Write>(mut self, mut writer: W, header: &Header) -> io::Result<W> {
+        let mut header_bytes = Vec::new();
+        ciborium::into_writer(header, &mut header_bytes)
+            .map_err(|error| io::Error::other(error.to_string()))?;
+
+        let node_order = self.nodes.order();
+        let [node_ids, _, _, node_tag_offsets, node_tags] = self.nodes.sections(&node_order);
+        let coordinates: Vec<u8> = node_order
+            .iter()
+            .flat_map(|&index| [self.coordinates[index * 2], self.coordinates[index * 2 + 1]])
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+
+        let way_order = self.ways.order();
+        let [way_ids, way_node_offsets, way_nodes, way_tag_offsets, way_tags] =
+            self.ways.sections(&way_order);
+
+        let relation_order = self.relations.order();
+        let [relation_ids, member_offsets, member_refs, relation_tag_offsets, relation_tags] =
+            self.relations.sections(&relation_order);
+        let member_items: Vec<usize> = relation_order
+            .iter()
+            .flat_map(|&index| self.relations.item_range(index))
+            .collect();
+        let member_types: Vec<u8> = member_items
+            .iter()
+            .map(|&member| self.member_types[member])
+            .collect();
+        let member_roles: Vec<u8> = member_items
+            .iter()
+            .flat_map(|&member| self.member_roles[member].to_le_bytes())
+            .collect();
+
+        self.strings.offsets.push(self.strings.bytes.len() as u64);
+        let string_offsets = u64s(self.strings.offsets.iter().cloned());
+
+        let sections: [&[u8]; SECTIONS] = [
+            &header_bytes,
+            &string_offsets,
+            &self.strings.bytes,
+            &node_ids,
+            &coordinates,
+            &node_tag_offsets,
+            &node_tags,
+            &way_ids,
+            &way_node_offsets,
+            &way_nodes,
+            &way_tag_offsets,
+            &way_tags,
+            &relation_ids,
+            &member_offsets,
+            &member_refs,
+            &member_types,
+            &member_roles,
+            &relation_tag_offsets,
+            &relation_tags,
+        ];
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        writer.write_all(&(SECTIONS as u32).to_le_bytes())?;
+        let padding = |length: usize| (8 - length % 8) % 8;
+        let mut offset = 16 + SECTIONS * 16;
+        for section in &sections {
+            writer.write_all(&(offset as u64).to_le_bytes())?;
+            writer.write_all(&(section.len() as u64).to_le_bytes())?;
+            offset += section.len() + padding(section.len());
+        }
+        for section in &sections {
+            writer.write_all(section)?;
+            writer.write_all(&[0u8; 8][..padding(section.len())])?;
+        }
+        Ok(writer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use crate::{binary::Header, dataset::Dataset, stream::XmlReader, MemberType};
+
+    use super::{MappedDataset, MappedWriter};
+
+    const OSM_XML: &str = r#"<osm version="0.6">
+  <node id="3" lat="47.1" lon="28.9"/>
+  <node id="1" lat="47.0" lon="28.8"><tag k="name" v="Fântână"/></node>
+  <node id="2" lat="47.1" lon="28.8"/>
+  <way id="11"><nd ref="2"/><nd ref="3"/></way>
+  <way id="10"><nd ref="1"/><nd ref="2"/><tag k="leisure" v="park"/></way>
+  <relation id="20">
+    <member type="way" ref="10" role="outer"/>
+    <member type="node" ref="3" role="label"/>
+    <tag k="leisure" v="park"/>
+  </relation>
+</osm>"#;
+
+    fn write(name: &str, bytes: &[u8]) -> MappedDataset {
+        let path =
+            std::env::temp_dir().join(format!("osm-tiles-{}-{}.map", name, std::process::id()));
+        std::fs::File::create(&path)
+            .unwrap()
+            .write_all(bytes)
+            .unwrap();
+        let dataset = MappedDataset::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        dataset
+    }
+
+    fn dataset_bytes() -> Vec<u8> {
+        let mut writer = MappedWriter::new();
+        let mut header = Header::new(Some("test.osm".to_string()));
+        XmlReader::new(OSM_XML.as_bytes()).for_each(|element| {
+            let element = element.unwrap();
+            header.count(&element);
+            writer.write(&element);
+        });
+        writer.finish(Vec::new(), &header).unwrap()
+    }
+
+    #[test]
+    fn write_and_map_dataset() {
+        let dataset = write("dataset", &dataset_bytes());
+
+        assert_eq!(dataset.header().source.as_deref(), Some("test.osm"));
+        assert_eq!(dataset.node(1), Some((47.0, 28.8)));
+        assert_eq!(dataset.node(4), None);
+        assert_eq!(
+            dataset.tagged_nodes().collect::<Vec<_>>(),
+            vec![(1, (47.0, 28.8))]
+        );
+        assert_eq!(dataset.ways().count(), 2);
+        let way = dataset.way(10).unwrap();
+        assert_eq!(
+            way.nd.iter().map(|nd| nd.reference).collect::<Vec<u64>>(),
+            vec![1, 2]
+        );
+        assert_eq!(way.tag.as_ref().unwrap()[0].v, "park");
+        assert!(dataset.way(11).unwrap().tag.is_none());
+        assert_eq!(
+            dataset.way_nodes().map(|(id, _)| id).collect::<Vec<u64>>(),
+            vec![10, 11]
+        );
+
+        let way = dataset.mapped_way(10).unwrap();
+        assert_eq!(way.nodes().collect::<Vec<u64>>(), vec![1, 2]);
+        assert_eq!(way.tags().collect::<Vec<_>>(), vec![("leisure", "park")]);
+        let relation = dataset.mapped_relation(20).unwrap();
+        assert_eq!(
+            relation.members().collect::<Vec<_>>(),
+            vec![
+                (MemberType::Way, 10, "outer"),
+                (MemberType::Node, 3, "label")
+            ]
+        );
+        assert_eq!(dataset.relations()[0].member[1].role, "label");
+
+        let osm = dataset.to_osm();
+        assert_eq!(
+            osm.node.iter().map(|node| node.id).collect::<Vec<u64>>(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(osm.node[0].tag.as_ref().unwrap()[0].v, "Fântână");
+    }
+
+    #[test]
+    fn ignore_elements_with_offsets_out_of_bounds() {
+        let mut bytes = dataset_bytes();
+        // the offset between the nodes of way 10 and way 11, only the last offset of a section is
+        // checked when the file is opened
+        let entry = 16 + super::WAY_NODE_OFFSETS * 16;
+        let section = u64::from_le_bytes(bytes[entry..entry + 8].try_into().unwrap()) as usize;
+        bytes[section + 8..section + 16].copy_from_slice(&u64::MAX.to_le_bytes());
+
+        let dataset = write("corrupt", &bytes);
+        assert!(dataset.way(10).is_none());
+        assert!(dataset.way(11).is_none());
+        assert_eq!(dataset.way_nodes().count(), 0);
+        assert_eq!(dataset.node(3), Some((47.1, 28.9)));
+    }
+}
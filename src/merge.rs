@@ -0,0 +1,187 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{geojson::synthetic_block, stream::MemoryReader, Bounds, Element, Node, Relation, Way};
+
+/// merges the elements of several inputs. Elements present in more than one input, like the ones
+/// on the border of neighbouring extracts, are kept once: the highest version wins, an element
+/// without version loses against a versioned one and on a tie the first one read is kept
+#[derive(Default)]
+pub struct Merge {
+    nodes: BTreeMap<u64, Node>,
+    ways: BTreeMap<u64, Way>,
+    relations: BTreeMap<u64, Relation>,
+    bounds: Option<Bounds>,
+    duplicates: usize,
+    /// blocks of the synthetic ids of the elements
+    synthetic_blocks: BTreeSet<u64>,
+}
+
+fn insert<T>(
+    elements: &mut BTreeMap<u64, T>,
+    id: u64,
+    element: T,
+    version: impl Fn(&T) -> Option<u32>,
+) -> bool {
+    match elements.get(&id) {
+        Some(existing) => {
+            if version(&element) > version(existing) {
+                elements.insert(id, element);
+            }
+            true
+        }
+        None => {
+            elements.insert(id, element);
+            false
+        }
+    }
+}
+
+impl Merge {
+    pub fn new() -> Self {
+        Merge::default()
+    }
+
+    pub fn add(&mut self, element: Element) {
+        if let Some(block) = synthetic_block(element.id()) {
+            self.synthetic_blocks.insert(block);
+        }
+        let duplicate = match element {
+            Element::Node(node) => {
+                insert(&mut self.nodes, node.id, node, |node| node.metadata.version)
+            }
+            Element::Way(way) => insert(&mut self.ways, way.id, way, |way| way.metadata.version),
+            Element::Relation(relation) => {
+                insert(&mut self.relations, relation.id, relation, |relation| {
+                    relation.metadata.version
+                })
+            }
+        };
+        if duplicate {
+            self.duplicates += 1;
+        }
+    }
+
+    /// extends the bounds of the merged dataset to include the bounds of an input
+    pub fn add_bounds(&mut self, bounds: &Bounds) {
+        self.bounds = Some(match self.bounds.take() {
+            Some(merged) => Bounds {
+                minlat: merged.minlat.min(bounds.minlat),
+                minlon: merged.minlon.min(bounds.minlon),
+                maxlat: merged.maxlat.max(bounds.maxlat),
+                maxlon: merged.maxlon.max(bounds.maxlon),
+            },
+            None => bounds.clone(),
+        });
+    }
+
+    /// first block of synthetic ids that none of the elements is in, for the next GeoJSON layer
+    pub fn free_synthetic_block(&self) -> u64 {
+        (0..)
+            .find(|block| !self.synthetic_blocks.contains(block))
+            .unwrap()
+    }
+
+    /// number of elements that were present in more than one input
+    pub fn duplicates(&self) -> usize {
+        self.duplicates
+    }
+
+    /// merged elements, nodes, ways and relations each sorted by id
+    pub fn into_reader(self) -> MemoryReader {
+        let nodes = self.nodes.into_values().map(Element::Node);
+        let ways = self.ways.into_values().map(Element::Way);
+        let relations = self.relations.into_values().map(Element::Relation);
+        MemoryReader::new(nodes.chain(ways).chain(relations), self.bounds, None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        geojson::read_geojson,
+        stream::{ElementReader, MemoryReader, XmlReader},
+        Element,
+    };
+
+    use super::Merge;
+
+    const WEST: &str = r#"<osm version="0.6">
+  <bounds minlat="46.9" minlon="28.7" maxlat="47.1" maxlon="28.9"/>
+  <node id="1" version="1" lat="47.0" lon="28.8"/>
+  <node id="2" version="3" lat="47.1" lon="28.9"/>
+  <way id="10" version="1"><nd ref="1"/><nd ref="2"/></way>
+</osm>"#;
+
+    const EAST: &str = r#"<osm version="0.6">
+  <bounds minlat="47.0" minlon="28.9" maxlat="47.2" maxlon="29.1"/>
+  <node id="2" version="2" lat="47.2" lon="29.0"/>
+  <node id="3" lat="47.1" lon="29.0"/>
+  <node id="1" version="2" lat="47.01" lon="28.81"/>
+  <way id="10" version="1"><nd ref="1"/><nd ref="2"/><nd ref="3"/></way>
+</osm>"#;
+
+    #[test]
+    fn merge_overlapping_extracts() {
+        let mut merge = Merge::new();
+        for input in [WEST, EAST] {
+            let mut reader = XmlReader::new(input.as_bytes());
+            reader
+                .by_ref()
+                .for_each(|element| merge.add(element.unwrap()));
+            merge.add_bounds(reader.bounds().unwrap());
+        }
+        assert_eq!(merge.duplicates(), 3);
+
+        let reader = merge.into_reader();
+        let bounds = reader.bounds().unwrap().clone();
+        assert_eq!((bounds.minlat, bounds.maxlon), (46.9, 29.1));
+        let elements: Vec<Element> = reader.map(|element| element.unwrap()).collect();
+        assert_eq!(
+            elements.iter().map(Element::id).collect::<Vec<u64>>(),
+            vec![1, 2, 3, 10]
+        );
+        let Element::Node(node) = &elements[0] else {
+            panic!("expected a node");
+        };
+        // the newer version of node 1 comes from the second input, node 2 is kept from the first
+        assert_eq!((node.metadata.version, node.lat), (Some(2), 47.01));
+        let Element::Node(node) = &elements[1] else {
+            panic!("expected a node");
+        };
+        assert_eq!(node.metadata.version, Some(3));
+        let Element::Way(way) = &elements[3] else {
+            panic!("expected a way");
+        };
+        assert_eq!(way.nd.len(), 2);
+    }
+
+    #[test]
+    fn give_geojson_layers_their_own_ids() {
+        // a dataset holding the first element of an earlier GeoJSON layer
+        const IMPORTED: &str = r#"<osm version="0.6">
+  <node id="9007199254740991" lat="47.0" lon="28.8"><tag k="amenity" v="cafe"/></node>
+</osm>"#;
+        const LAYER: &str = r#"{"type": "Point", "coordinates": [28.9, 47.1]}"#;
+
+        let mut merge = Merge::new();
+        assert_eq!(merge.free_synthetic_block(), 0);
+        XmlReader::new(IMPORTED.as_bytes()).for_each(|element| merge.add(element.unwrap()));
+        for block in [1, 2] {
+            assert_eq!(merge.free_synthetic_block(), block);
+            MemoryReader::from(read_geojson(LAYER.as_bytes(), block).unwrap())
+                .for_each(|element| merge.add(element.unwrap()));
+        }
+        assert_eq!(merge.duplicates(), 0);
+        assert_eq!(
+            merge
+                .into_reader()
+                .map(|element| element.unwrap().id())
+                .collect::<Vec<u64>>(),
+            vec![
+                (1 << 53) - 1 - 2 * (1 << 32),
+                (1 << 53) - 1 - (1 << 32),
+                (1 << 53) - 1
+            ]
+        );
+    }
+}
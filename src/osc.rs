@@ -0,0 +1,309 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fs,
+    io::{self, BufRead},
+    ops::RangeInclusive,
+    path::Path,
+    sync::Arc,
+};
+
+use crate::{
+    stream::XmlReader,
+    utils::{convert_to_tile, resolve_members, tiles_for_pixel},
+    Element, MemberType, Node, Osm, Relation, Way, TILE_SIZE,
+};
+
+/// tiles to expire for each zoom level
+pub type AffectedTiles = BTreeMap<u8, BTreeSet<(i32, i32)>>;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Create,
+    Modify,
+    Delete,
+}
+
+pub struct Change {
+    pub action: Action,
+    pub element: Element,
+}
+
+/// reads the create, modify and delete blocks of an OsmChange (.osc) document
+pub fn read_osc<R: BufRead>(reader: R) -> io::Result<Vec<Change>> {
+    let mut reader = XmlReader::new(reader);
+    let mut changes = Vec::new();
+    while let Some(element) = reader.next() {
+        let element = element?;
+        let action = reader.action().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "element {} outside of a create, modify or delete block",
+                    element.id()
+                ),
+            )
+        })?;
+        changes.push(Change { action, element });
+    }
+    Ok(changes)
+}
+
+struct Dataset {
+    nodes: HashMap<u64, Arc<Node>>,
+    ways: HashMap<u64, Arc<Way>>,
+    relations: HashMap<u64, Arc<Relation>>,
+}
+
+impl Dataset {
+    /// coordinates of the touched nodes, of the nodes of the touched ways and of the nodes of
+    /// the way and node members of the touched relations, including their nested relations
+    fn coordinates(
+        &self,
+        nodes: &HashSet<u64>,
+        ways: &HashSet<u64>,
+        relations: &HashSet<u64>,
+    ) -> Vec<(f64, f64)> {
+        let way_nodes = |way_id: &u64| {
+            self.ways
+                .get(way_id)
+                .into_iter()
+                .flat_map(|way| way.nd.iter().map(|nd| nd.reference))
+        };
+        let relation_nodes = relations
+            .iter()
+            .flat_map(|relation_id| self.relations.get(relation_id))
+            .flat_map(|relation| resolve_members(relation, &self.relations))
+            .flat_map(|member| match member.member_type {
+                MemberType::Node => vec![member.member_ref],
+                MemberType::Way => way_nodes(&member.member_ref).collect(),
+                MemberType::Relation => vec![],
+            });
+
+        nodes
+            .iter()
+            .cloned()
+            .chain(ways.iter().flat_map(way_nodes))
+            .chain(relation_nodes)
+            .flat_map(|node_id| self.nodes.get(&node_id))
+            .filter(|node| !node.lat.is_nan() && !node.lon.is_nan())
+            .map(|node| (node.lat, node.lon))
+            .collect()
+    }
+}
+
+fn add_tiles(tiles: &mut AffectedTiles, coordinates: &[(f64, f64)], zooms: &RangeInclusive<u8>) {
+    for zoom in zooms.clone() {
+        let dimension_in_pixels_for_zoom = f64::from(TILE_SIZE * (1 << zoom));
+        let zoom_tiles = tiles.entry(zoom).or_default();
+        coordinates.iter().for_each(|(lat, lon)| {
+            let (x, y) = convert_to_tile(*lat, *lon);
+            zoom_tiles.extend(tiles_for_pixel(
+                x * dimension_in_pixels_for_zoom,
+                y * dimension_in_pixels_for_zoom,
+                zoom,
+            ));
+        });
+    }
+}
+
+/// applies the changes to the dataset and returns the tiles whose rendering changes, both the
+/// old and the new geometry of every touched element are taken into account. Ways referencing a
+/// touched node and relations referencing a touched node, way or relation are considered touched
+/// as well.
+pub fn apply_changes(
+    osm: &mut Osm,
+    changes: Vec<Change>,
+    zooms: RangeInclusive<u8>,
+) -> AffectedTiles {
+    let mut dataset = Dataset {
+        nodes: osm.node.drain(..).map(|node| (node.id, node)).collect(),
+        ways: osm.way.drain(..).map(|way| (way.id, way)).collect(),
+        relations: osm
+            .relation
+            .drain(..)
+            .map(|relation| (relation.id, relation))
+            .collect(),
+    };
+
+    let mut touched_nodes = HashSet::<u64>::new();
+    let mut touched_ways = HashSet::<u64>::new();
+    let mut touched_relations = HashSet::<u64>::new();
+    changes.iter().for_each(|change| match &change.element {
+        Element::Node(node) => {
+            touched_nodes.insert(node.id);
+        }
+        Element::Way(way) => {
+            touched_ways.insert(way.id);
+        }
+        Element::Relation(relation) => {
+            touched_relations.insert(relation.id);
+        }
+    });
+    dataset
+        .ways
+        .values()
+        .filter(|way| {
+            way.nd
+                .iter()
+                .any(|nd| touched_nodes.contains(&nd.reference))
+        })
+        .for_each(|way| {
+            touched_ways.insert(way.id);
+        });
+    // parents of touched relations are touched as well, repeat until no relation is added
+    loop {
+        let parents: Vec<u64> = dataset
+            .relations
+            .values()
+            .filter(|relation| !touched_relations.contains(&relation.id))
+            .filter(|relation| {
+                relation
+                    .member
+                    .iter()
+                    .any(|member| match member.member_type {
+                        MemberType::Node => touched_nodes.contains(&member.member_ref),
+                        MemberType::Way => touched_ways.contains(&member.member_ref),
+                        MemberType::Relation => touched_relations.contains(&member.member_ref),
+                    })
+            })
+            .map(|relation| relation.id)
+            .collect();
+        if parents.is_empty() {
+            break;
+        }
+        touched_relations.extend(parents);
+    }
+
+    let mut tiles = AffectedTiles::new();
+    add_tiles(
+        &mut tiles,
+        &dataset.coordinates(&touched_nodes, &touched_ways, &touched_relations),
+        &zooms,
+    );
+
+    for change in changes {
+        match (change.action, change.element) {
+            (Action::Delete, Element::Node(node)) => {
+                dataset.nodes.remove(&node.id);
+            }
+            (Action::Delete, Element::Way(way)) => {
+                dataset.ways.remove(&way.id);
+            }
+            (Action::Delete, Element::Relation(relation)) => {
+                dataset.relations.remove(&relation.id);
+            }
+            (_, Element::Node(node)) => {
+                dataset.nodes.insert(node.id, Arc::new(node));
+            }
+            (_, Element::Way(way)) => {
+                dataset.ways.insert(way.id, Arc::new(way));
+            }
+            (_, Element::Relation(relation)) => {
+                dataset.relations.insert(relation.id, Arc::new(relation));
+            }
+        }
+    }
+
+    add_tiles(
+        &mut tiles,
+        &dataset.coordinates(&touched_nodes, &touched_ways, &touched_relations),
+        &zooms,
+    );
+
+    osm.node = dataset.nodes.into_values().collect();
+    osm.node.sort_by_key(|node| node.id);
+    osm.way = dataset.ways.into_values().collect();
+    osm.way.sort_by_key(|way| way.id);
+    osm.relation = dataset.relations.into_values().collect();
+    osm.relation.sort_by_key(|relation| relation.id);
+
+    tiles
+}
+
+/// removes the rendered tiles from a `z/x/y.png` tile cache, returns the number of removed files
+pub fn expire_tiles(cache_dir: &Path, tiles: &AffectedTiles) -> io::Result<usize> {
+    let mut removed = 0;
+    for (zoom, zoom_tiles) in tiles {
+        for (x, y) in zoom_tiles {
+            match fs::remove_file(cache_dir.join(format!("{}/{}/{}.png", zoom, x, y))) {
+                Ok(()) => removed += 1,
+                Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+                Err(error) => return Err(error),
+            }
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{stream::XmlReader, Osm};
+
+    use super::{apply_changes, read_osc, Action};
+
+    const OSM_XML: &str = r#"<osm version="0.6">
+  <node id="1" lat="47.0" lon="28.8"/>
+  <node id="2" lat="47.1" lon="28.8"/>
+  <node id="3" lat="47.1" lon="28.9"/>
+  <way id="10"><nd ref="1"/><nd ref="2"/></way>
+  <way id="11"><nd ref="2"/><nd ref="3"/></way>
+</osm>"#;
+
+    const OSC_XML: &str = r#"<osmChange version="0.6">
+  <create>
+    <node id="4" lat="46.5" lon="29.5"/>
+    <way id="12"><nd ref="3"/><nd ref="4"/><tag k="highway" v="primary"/></way>
+  </create>
+  <modify>
+    <node id="1" lat="47.01" lon="28.81"/>
+  </modify>
+  <delete>
+    <way id="11"/>
+    <node id="5"/>
+  </delete>
+</osmChange>"#;
+
+    #[test]
+    fn apply_change_file() {
+        let mut osm = Osm::default();
+        XmlReader::new(OSM_XML.as_bytes()).for_each(|element| osm.push(element.unwrap()));
+
+        let changes = read_osc(OSC_XML.as_bytes()).unwrap();
+        assert_eq!(
+            changes
+                .iter()
+                .map(|change| change.action)
+                .collect::<Vec<Action>>(),
+            vec![
+                Action::Create,
+                Action::Create,
+                Action::Modify,
+                Action::Delete,
+                Action::Delete
+            ]
+        );
+
+        let tiles = apply_changes(&mut osm, changes, 10..=12);
+
+        assert_eq!(
+            osm.node.iter().map(|node| node.id).collect::<Vec<u64>>(),
+            vec![1, 2, 3, 4]
+        );
+        assert_eq!(osm.node[0].lat, 47.01);
+        assert_eq!(
+            osm.way.iter().map(|way| way.id).collect::<Vec<u64>>(),
+            vec![10, 12]
+        );
+
+        // way 12 spans from node 3 to node 4, deleted way 11 from node 2 to node 3
+        let z10 = tiles.get(&10).unwrap();
+        let tile = |lat: f64, lon: f64| {
+            let (x, y) = crate::utils::convert_to_tile(lat, lon);
+            crate::utils::convert_to_int_tile(x * 256.0 * 1024.0, y * 256.0 * 1024.0)
+        };
+        assert!(z10.contains(&tile(46.5, 29.5)));
+        assert!(z10.contains(&tile(47.1, 28.9)));
+        assert!(z10.contains(&tile(47.1, 28.8)));
+        assert_eq!(tiles.keys().cloned().collect::<Vec<u8>>(), vec![10, 11, 12]);
+    }
+}
@@ -0,0 +1,723 @@
+use std::{
+    cell::OnceCell,
+    collections::VecDeque,
+    io::{self, Read},
+};
+
+use flate2::read::ZlibDecoder;
+use prost::Message;
+
+use crate::{
+    stream::ElementReader, Bounds, Element, Member, MemberType, Metadata, Nd, Node, Osm, Relation,
+    Symbol, Tag, Way,
+};
+
+const MAX_BLOB_HEADER_SIZE: u32 = 64 * 1024;
+const MAX_BLOB_SIZE: i32 = 32 * 1024 * 1024;
+const SUPPORTED_FEATURES: [&str; 3] = ["OsmSchema-V0.6", "DenseNodes", "HistoricalInformation"];
+
+// messages from fileformat.proto and osmformat.proto, only the fields used by the importer are
+// declared, prost skips the rest
+
+#[derive(Clone, PartialEq, Message)]
+struct BlobHeader {
+    #[prost(string, required, tag = "1")]
+    blob_type: String,
+    #[prost(int32, required, tag = "3")]
+    datasize: i32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Blob {
+    #[prost(bytes = "vec", optional, tag = "1")]
+    raw: Option<Vec<u8>>,
+    #[prost(int32, optional, tag = "2")]
+    raw_size: Option<i32>,
+    #[prost(bytes = "vec", optional, tag = "3")]
+    zlib_data: Option<Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct HeaderBlock {
+    #[prost(message, optional, tag = "1")]
+    bbox: Option<HeaderBBox>,
+    #[prost(string, repeated, tag = "4")]
+    required_features: Vec<String>,
+    #[prost(string, optional, tag = "16")]
+    writingprogram: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct HeaderBBox {
+    #[prost(sint64, required, tag = "1")]
+    left: i64,
+    #[prost(sint64, required, tag = "2")]
+    right: i64,
+    #[prost(sint64, required, tag = "3")]
+    top: i64,
+    #[prost(sint64, required, tag = "4")]
+    bottom: i64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PrimitiveBlock {
+    #[prost(message, required, tag = "1")]
+    stringtable: StringTable,
+    #[prost(message, repeated, tag = "2")]
+    primitivegroup: Vec<PrimitiveGroup>,
+    #[prost(int32, optional, tag = "17", default = "100")]
+    granularity: Option<i32>,
+    #[prost(int32, optional, tag = "18", default = "1000")]
+    date_granularity: Option<i32>,
+    #[prost(int64, optional, tag = "19", default = "0")]
+    lat_offset: Option<i64>,
+    #[prost(int64, optional, tag = "20", default = "0")]
+    lon_offset: Option<i64>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct StringTable {
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    s: Vec<Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Info {
+    #[prost(int32, optional, tag = "1", default = "-1")]
+    version: Option<i32>,
+    #[prost(int64, optional, tag = "2")]
+    timestamp: Option<i64>,
+    #[prost(int64, optional, tag = "3")]
+    changeset: Option<i64>,
+    #[prost(int32, optional, tag = "4")]
+    uid: Option<i32>,
+    #[prost(uint32, optional, tag = "5")]
+    user_sid: Option<u32>,
+    #[prost(bool, optional, tag = "6")]
+    visible: Option<bool>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct DenseInfo {
+    #[prost(int32, repeated, packed = "true", tag = "1")]
+    version: Vec<i32>,
+    #[prost(sint64, repeated, packed = "true", tag = "2")]
+    timestamp: Vec<i64>,
+    #[prost(sint64, repeated, packed = "true", tag = "3")]
+    changeset: Vec<i64>,
+    #[prost(sint32, repeated, packed = "true", tag = "4")]
+    uid: Vec<i32>,
+    #[prost(sint32, repeated, packed = "true", tag = "5")]
+    user_sid: Vec<i32>,
+    #[prost(bool, repeated, packed = "true", tag = "6")]
+    visible: Vec<bool>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PrimitiveGroup {
+    #[prost(message, repeated, tag = "1")]
+    nodes: Vec<PbfNode>,
+    #[prost(message, optional, tag = "2")]
+    dense: Option<DenseNodes>,
+    #[prost(message, repeated, tag = "3")]
+    ways: Vec<PbfWay>,
+    #[prost(message, repeated, tag = "4")]
+    relations: Vec<PbfRelation>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PbfNode {
+    #[prost(sint64, required, tag = "1")]
+    id: i64,
+    #[prost(uint32, repeated, packed = "true", tag = "2")]
+    keys: Vec<u32>,
+    #[prost(uint32, repeated, packed = "true", tag = "3")]
+    vals: Vec<u32>,
+    #[prost(message, optional, tag = "4")]
+    info: Option<Info>,
+    #[prost(sint64, required, tag = "8")]
+    lat: i64,
+    #[prost(sint64, required, tag = "9")]
+    lon: i64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct DenseNodes {
+    #[prost(sint64, repeated, packed = "true", tag = "1")]
+    id: Vec<i64>,
+    #[prost(message, optional, tag = "5")]
+    denseinfo: Option<DenseInfo>,
+    #[prost(sint64, repeated, packed = "true", tag = "8")]
+    lat: Vec<i64>,
+    #[prost(sint64, repeated, packed = "true", tag = "9")]
+    lon: Vec<i64>,
+    #[prost(int32, repeated, packed = "true", tag = "10")]
+    keys_vals: Vec<i32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PbfWay {
+    #[prost(int64, required, tag = "1")]
+    id: i64,
+    #[prost(uint32, repeated, packed = "true", tag = "2")]
+    keys: Vec<u32>,
+    #[prost(uint32, repeated, packed = "true", tag = "3")]
+    vals: Vec<u32>,
+    #[prost(message, optional, tag = "4")]
+    info: Option<Info>,
+    #[prost(sint64, repeated, packed = "true", tag = "8")]
+    refs: Vec<i64>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PbfRelation {
+    #[prost(int64, required, tag = "1")]
+    id: i64,
+    #[prost(uint32, repeated, packed = "true", tag = "2")]
+    keys: Vec<u32>,
+    #[prost(uint32, repeated, packed = "true", tag = "3")]
+    vals: Vec<u32>,
+    #[prost(message, optional, tag = "4")]
+    info: Option<Info>,
+    #[prost(int32, repeated, packed = "true", tag = "8")]
+    roles_sid: Vec<i32>,
+    #[prost(sint64, repeated, packed = "true", tag = "9")]
+    memids: Vec<i64>,
+    #[prost(int32, repeated, packed = "true", tag = "10")]
+    types: Vec<i32>,
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn to_id(id: i64) -> io::Result<u64> {
+    u64::try_from(id).map_err(|_| invalid_data(format!("negative element id {}", id)))
+}
+
+/// formats seconds since the epoch the way OSM XML does, `2023-01-31T12:00:00Z`
+pub(crate) fn format_timestamp(seconds: i64) -> String {
+    let (days, seconds) = (seconds.div_euclid(86_400), seconds.rem_euclid(86_400));
+    // civil_from_days from http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// reads elements out of an .osm.pbf stream, only the elements of the current `OSMData` blob are
+/// kept in memory
+pub struct PbfReader<R: Read> {
+    reader: R,
+    elements: VecDeque<Element>,
+    bounds: Option<Bounds>,
+    generator: Option<String>,
+}
+
+impl<R: Read> PbfReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            elements: VecDeque::new(),
+            bounds: None,
+            generator: None,
+        }
+    }
+
+    /// returns the type and the decompressed content of the next blob, `None` at the end of the
+    /// stream
+    fn next_blob(&mut self) -> io::Result<Option<(String, Vec<u8>)>> {
+        let mut size = [0u8; 4];
+        match self.reader.read_exact(&mut size) {
+            Ok(()) => {}
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(error) => return Err(error),
+        }
+        let size = u32::from_be_bytes(size);
+        if size > MAX_BLOB_HEADER_SIZE {
+            return Err(invalid_data(format!("blob header too big {}", size)));
+        }
+
+        let mut buffer = vec![0u8; size as usize];
+        self.reader.read_exact(&mut buffer)?;
+        let header = BlobHeader::decode(buffer.as_slice())?;
+        if !(0..=MAX_BLOB_SIZE).contains(&header.datasize) {
+            return Err(invalid_data(format!(
+                "invalid blob size {}",
+                header.datasize
+            )));
+        }
+
+        let mut buffer = vec![0u8; header.datasize as usize];
+        self.reader.read_exact(&mut buffer)?;
+        let blob = Blob::decode(buffer.as_slice())?;
+
+        let data = if let Some(raw) = blob.raw {
+            raw
+        } else if let Some(zlib_data) = blob.zlib_data {
+            let mut data = Vec::with_capacity(blob.raw_size.unwrap_or(0).max(0) as usize);
+            ZlibDecoder::new(zlib_data.as_slice()).read_to_end(&mut data)?;
+            data
+        } else {
+            return Err(invalid_data("unsupported blob compression"));
+        };
+        Ok(Some((header.blob_type, data)))
+    }
+
+    /// decodes the next data block into nodes, ways and relations, `false` at the end of the
+    /// stream
+    fn read_block(&mut self) -> io::Result<bool> {
+        while let Some((blob_type, data)) = self.next_blob()? {
+            match blob_type.as_str() {
+                "OSMHeader" => {
+                    let header = HeaderBlock::decode(data.as_slice())?;
+                    if let Some(feature) = header
+                        .required_features
+                        .iter()
+                        .find(|feature| !SUPPORTED_FEATURES.contains(&feature.as_str()))
+                    {
+                        return Err(invalid_data(format!("unsupported feature {}", feature)));
+                    }
+                    self.generator = header.writingprogram;
+                    self.bounds = header.bbox.map(|bbox| Bounds {
+                        minlat: bbox.bottom as f64 / 1e9,
+                        minlon: bbox.left as f64 / 1e9,
+                        maxlat: bbox.top as f64 / 1e9,
+                        maxlon: bbox.right as f64 / 1e9,
+                    });
+                }
+                "OSMData" => {
+                    decode_block(
+                        &PrimitiveBlock::decode(data.as_slice())?,
+                        &mut self.elements,
+                    )?;
+                    return Ok(true);
+                }
+                // unknown blobs are allowed by the spec and have to be skipped
+                _ => {}
+            }
+        }
+        Ok(false)
+    }
+}
+
+impl<R: Read> Iterator for PbfReader<R> {
+    type Item = io::Result<Element>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.elements.is_empty() {
+            match self.read_block() {
+                Ok(true) => {}
+                Ok(false) => return None,
+                Err(error) => return Some(Err(error)),
+            }
+        }
+        self.elements.pop_front().map(Ok)
+    }
+}
+
+impl<R: Read> ElementReader for PbfReader<R> {
+    fn bounds(&self) -> Option<&Bounds> {
+        self.bounds.as_ref()
+    }
+
+    fn generator(&self) -> Option<&str> {
+        self.generator.as_deref()
+    }
+}
+
+/// reads a whole .osm.pbf file into memory
+pub fn read_pbf<R: Read>(reader: R) -> io::Result<Osm> {
+    let mut reader = PbfReader::new(reader);
+    let mut osm = Osm::default();
+    for element in reader.by_ref() {
+        osm.push(element?);
+    }
+    osm.bounds = reader.bounds;
+    osm.generator = reader.generator;
+    Ok(osm)
+}
+
+fn decode_block(block: &PrimitiveBlock, elements: &mut VecDeque<Element>) -> io::Result<()> {
+    let strings = block
+        .stringtable
+        .s
+        .iter()
+        .map(|s| String::from_utf8_lossy(s).into_owned())
+        .collect::<Vec<String>>();
+    // only the strings of tag keys and values are interned, the first time a tag uses them in
+    // the block. User names and roles stay plain strings
+    let symbols: Vec<OnceCell<Symbol>> = strings.iter().map(|_| OnceCell::new()).collect();
+    let symbol = |index: u32| {
+        symbols
+            .get(index as usize)
+            .map(|symbol| *symbol.get_or_init(|| Symbol::new(&strings[index as usize])))
+            .ok_or_else(|| invalid_data(format!("string index out of range {}", index)))
+    };
+    let string = |index: i64| {
+        usize::try_from(index)
+            .ok()
+            .and_then(|index| strings.get(index))
+            .cloned()
+            .ok_or_else(|| invalid_data(format!("string index out of range {}", index)))
+    };
+    let tags = |keys: &[u32], vals: &[u32]| -> io::Result<Option<Vec<Tag>>> {
+        if keys.is_empty() {
+            return Ok(None);
+        }
+        keys.iter()
+            .zip(vals.iter())
+            .map(|(k, v)| {
+                Ok(Tag {
+                    k: symbol(*k)?,
+                    v: symbol(*v)?,
+                })
+            })
+            .collect::<io::Result<Vec<Tag>>>()
+            .map(Some)
+    };
+
+    let date_granularity = i64::from(block.date_granularity());
+    let user = |index: i64| -> io::Result<Option<String>> {
+        Ok(Some(string(index)?).filter(|user| !user.is_empty()))
+    };
+    let metadata = |info: &Option<Info>| -> io::Result<Metadata> {
+        let Some(info) = info else {
+            return Ok(Metadata::default());
+        };
+        Ok(Metadata {
+            version: u32::try_from(info.version()).ok(),
+            timestamp: info
+                .timestamp
+                .map(|timestamp| format_timestamp(timestamp * date_granularity / 1000)),
+            changeset: info
+                .changeset
+                .and_then(|changeset| u64::try_from(changeset).ok()),
+            user: info
+                .user_sid
+                .map(|user_sid| user(i64::from(user_sid)))
+                .transpose()?
+                .flatten(),
+            uid: info.uid.and_then(|uid| u64::try_from(uid).ok()),
+            visible: info.visible,
+        })
+    };
+
+    let granularity = block.granularity() as f64;
+    let (lat_offset, lon_offset) = (block.lat_offset() as f64, block.lon_offset() as f64);
+    let to_degrees = |offset: f64, value: i64| 1e-9 * (offset + granularity * value as f64);
+
+    for group in &block.primitivegroup {
+        for node in &group.nodes {
+            elements.push_back(Element::Node(Node {
+                id: to_id(node.id)?,
+                lat: to_degrees(lat_offset, node.lat),
+                lon: to_degrees(lon_offset, node.lon),
+                tag: tags(&node.keys, &node.vals)?,
+                metadata: metadata(&node.info)?,
+            }));
+        }
+
+        if let Some(dense) = &group.dense {
+            // ids and coordinates are delta coded, tags are a flat list of key, value pairs
+            // where each node's list is terminated by a 0. All of the dense info is delta coded
+            // as well, except for the version and visible flags
+            let mut keys_vals = dense.keys_vals.iter();
+            let (mut id, mut lat, mut lon) = (0i64, 0i64, 0i64);
+            let (mut timestamp, mut changeset, mut uid, mut user_sid) = (0i64, 0i64, 0i32, 0i32);
+            for (index, ((delta_id, delta_lat), delta_lon)) in dense
+                .id
+                .iter()
+                .zip(dense.lat.iter())
+                .zip(dense.lon.iter())
+                .enumerate()
+            {
+                id += delta_id;
+                lat += delta_lat;
+                lon += delta_lon;
+
+                let node_metadata = if let Some(info) = &dense.denseinfo {
+                    timestamp += info.timestamp.get(index).unwrap_or(&0);
+                    changeset += info.changeset.get(index).unwrap_or(&0);
+                    uid += info.uid.get(index).unwrap_or(&0);
+                    user_sid += info.user_sid.get(index).unwrap_or(&0);
+                    Metadata {
+                        version: info
+                            .version
+                            .get(index)
+                            .and_then(|version| u32::try_from(*version).ok()),
+                        timestamp: (!info.timestamp.is_empty())
+                            .then(|| format_timestamp(timestamp * date_granularity / 1000)),
+                        changeset: (!info.changeset.is_empty())
+                            .then(|| u64::try_from(changeset).ok())
+                            .flatten(),
+                        user: if info.user_sid.is_empty() {
+                            None
+                        } else {
+                            user(i64::from(user_sid))?
+                        },
+                        uid: (!info.uid.is_empty())
+                            .then(|| u64::try_from(uid).ok())
+                            .flatten(),
+                        visible: info.visible.get(index).cloned(),
+                    }
+                } else {
+                    Metadata::default()
+                };
+
+                let mut tag = Vec::<Tag>::new();
+                while let Some(&k) = keys_vals.next() {
+                    if k == 0 {
+                        break;
+                    }
+                    let v = *keys_vals
+                        .next()
+                        .ok_or_else(|| invalid_data("dense node tag without value"))?;
+                    tag.push(Tag {
+                        k: symbol(k as u32)?,
+                        v: symbol(v as u32)?,
+                    });
+                }
+
+                elements.push_back(Element::Node(Node {
+                    id: to_id(id)?,
+                    lat: to_degrees(lat_offset, lat),
+                    lon: to_degrees(lon_offset, lon),
+                    tag: if tag.is_empty() { None } else { Some(tag) },
+                    metadata: node_metadata,
+                }));
+            }
+        }
+
+        for way in &group.ways {
+            let mut reference = 0i64;
+            let nd = way
+                .refs
+                .iter()
+                .map(|delta| {
+                    reference += delta;
+                    Ok(Nd {
+                        reference: to_id(reference)?,
+                    })
+                })
+                .collect::<io::Result<Vec<Nd>>>()?;
+            elements.push_back(Element::Way(Way {
+                id: to_id(way.id)?,
+                nd,
+                tag: tags(&way.keys, &way.vals)?,
+                metadata: metadata(&way.info)?,
+            }));
+        }
+
+        for relation in &group.relations {
+            let mut member_ref = 0i64;
+            let member = relation
+                .memids
+                .iter()
+                .zip(relation.types.iter())
+                .zip(relation.roles_sid.iter())
+                .map(|((delta, member_type), role)| {
+                    member_ref += delta;
+                    let member_type = match member_type {
+                        0 => MemberType::Node,
+                        1 => MemberType::Way,
+                        2 => MemberType::Relation,
+                        _ => {
+                            return Err(invalid_data(format!(
+                                "unknown member type {}",
+                                member_type
+                            )))
+                        }
+                    };
+                    Ok(Member {
+                        member_type,
+                        member_ref: to_id(member_ref)?,
+                        role: string(i64::from(*role))?,
+                        tag: None,
+                    })
+                })
+                .collect::<io::Result<Vec<Member>>>()?;
+            elements.push_back(Element::Relation(Relation {
+                id: to_id(relation.id)?,
+                member,
+                tag: tags(&relation.keys, &relation.vals)?,
+                metadata: metadata(&relation.info)?,
+            }));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use flate2::{write::ZlibEncoder, Compression};
+    use prost::Message;
+
+    use crate::MemberType;
+
+    use super::{
+        read_pbf, Blob, BlobHeader, DenseInfo, DenseNodes, HeaderBBox, HeaderBlock, Info,
+        PbfRelation, PbfWay, PrimitiveBlock, PrimitiveGroup, StringTable,
+    };
+
+    fn write_blob(out: &mut Vec<u8>, blob_type: &str, data: Vec<u8>) {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&data).unwrap();
+        let blob = Blob {
+            raw: None,
+            raw_size: Some(data.len() as i32),
+            zlib_data: Some(encoder.finish().unwrap()),
+        }
+        .encode_to_vec();
+        let header = BlobHeader {
+            blob_type: blob_type.to_string(),
+            datasize: blob.len() as i32,
+        }
+        .encode_to_vec();
+        out.extend((header.len() as u32).to_be_bytes());
+        out.extend(header);
+        out.extend(blob);
+    }
+
+    #[test]
+    fn read_dense_nodes_ways_and_relations() {
+        let strings = ["", "leisure", "park", "outer", "name", "mapper"];
+        let block = PrimitiveBlock {
+            stringtable: StringTable {
+                s: strings.iter().map(|s| s.as_bytes().to_vec()).collect(),
+            },
+            primitivegroup: vec![
+                PrimitiveGroup {
+                    dense: Some(DenseNodes {
+                        id: vec![10, 1, 1],
+                        denseinfo: Some(DenseInfo {
+                            version: vec![1, 2, 3],
+                            timestamp: vec![1_672_531_200, 60, 0],
+                            changeset: vec![100, 1, 1],
+                            uid: vec![7, 0, 1],
+                            user_sid: vec![5, 0, 0],
+                            visible: vec![],
+                        }),
+                        lat: vec![470_000_000, 10, -20],
+                        lon: vec![288_000_000, 5, 5],
+                        keys_vals: vec![0, 4, 2, 0, 0],
+                    }),
+                    ..Default::default()
+                },
+                PrimitiveGroup {
+                    ways: vec![PbfWay {
+                        id: 20,
+                        keys: vec![1],
+                        vals: vec![2],
+                        info: Some(Info {
+                            version: Some(4),
+                            timestamp: Some(1_700_000_000),
+                            changeset: Some(200),
+                            uid: Some(7),
+                            user_sid: Some(5),
+                            visible: None,
+                        }),
+                        refs: vec![10, 1, 1, -2],
+                    }],
+                    relations: vec![PbfRelation {
+                        id: 30,
+                        keys: vec![1],
+                        vals: vec![2],
+                        info: None,
+                        roles_sid: vec![3],
+                        memids: vec![20],
+                        types: vec![1],
+                    }],
+                    ..Default::default()
+                },
+            ],
+            granularity: None,
+            date_granularity: None,
+            lat_offset: None,
+            lon_offset: None,
+        };
+
+        let mut file = Vec::new();
+        write_blob(
+            &mut file,
+            "OSMHeader",
+            HeaderBlock {
+                bbox: Some(HeaderBBox {
+                    left: 28_700_000_000,
+                    right: 28_900_000_000,
+                    top: 47_100_000_000,
+                    bottom: 46_900_000_000,
+                }),
+                required_features: vec!["OsmSchema-V0.6".to_string(), "DenseNodes".to_string()],
+                writingprogram: Some("test".to_string()),
+            }
+            .encode_to_vec(),
+        );
+        write_blob(&mut file, "OSMData", block.encode_to_vec());
+
+        let osm = read_pbf(file.as_slice()).unwrap();
+
+        assert_eq!(
+            osm.node.iter().map(|node| node.id).collect::<Vec<u64>>(),
+            vec![10, 11, 12]
+        );
+        assert!((osm.node[1].lat - 47.000001).abs() < 1e-9);
+        assert!((osm.node[2].lon - 28.800001).abs() < 1e-9);
+        assert!(osm.node[0].tag.is_none());
+        assert_eq!(osm.node[1].tag.as_ref().unwrap()[0].k, "name");
+        assert_eq!(osm.node[1].tag.as_ref().unwrap()[0].v, "park");
+        assert_eq!(osm.node[0].metadata.version, Some(1));
+        assert_eq!(
+            osm.node[0].metadata.timestamp.as_deref(),
+            Some("2023-01-01T00:00:00Z")
+        );
+        assert_eq!(
+            osm.node[1].metadata.timestamp.as_deref(),
+            Some("2023-01-01T00:01:00Z")
+        );
+        assert_eq!(osm.node[2].metadata.changeset, Some(102));
+        assert_eq!(osm.node[2].metadata.uid, Some(8));
+        assert_eq!(osm.node[2].metadata.user.as_deref(), Some("mapper"));
+        assert_eq!(osm.node[2].metadata.visible, None);
+        assert_eq!(osm.generator.as_deref(), Some("test"));
+        assert_eq!(osm.bounds.as_ref().unwrap().minlat, 46.9);
+
+        let way = &osm.way[0];
+        assert_eq!(
+            way.nd.iter().map(|nd| nd.reference).collect::<Vec<u64>>(),
+            vec![10, 11, 12, 10]
+        );
+        assert_eq!(way.tag.as_ref().unwrap()[0].k, "leisure");
+        assert_eq!(way.metadata.version, Some(4));
+        assert_eq!(
+            way.metadata.timestamp.as_deref(),
+            Some("2023-11-14T22:13:20Z")
+        );
+
+        let relation = &osm.relation[0];
+        assert_eq!(relation.member[0].member_type, MemberType::Way);
+        assert_eq!(relation.member[0].member_ref, 20);
+        assert_eq!(relation.member[0].role, "outer");
+    }
+}
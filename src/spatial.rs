@@ -0,0 +1,306 @@
+use std::{collections::HashMap, f64::consts::PI, sync::Arc};
+
+use rstar::{PointDistance, RTree, RTreeObject, AABB};
+
+use crate::{
+    dataset::{Dataset, MemoryDataset},
+    tags::Tagged,
+    utils::convert_to_tile,
+    utils::nested_relation_ids,
+    Bounds, ElementId, MemberType, Osm, Relation,
+};
+
+/// equatorial circumference of the web mercator projection, in metres
+const EARTH_CIRCUMFERENCE: f64 = 2.0 * PI * 6_378_137.0;
+
+/// latitude of the northern edge of the web mercator projection, the poles are infinitely far
+const MAX_LATITUDE: f64 = 85.051_128_779_806_59;
+
+/// world coordinates of `convert_to_tile`, between 0 and 1 and with y growing to the south.
+/// Latitudes beyond the edges of the projection are clamped to them
+type WorldPoint = [f64; 2];
+
+fn world_point(lat: f64, lon: f64) -> WorldPoint {
+    let (x, y) = convert_to_tile(lat.clamp(-MAX_LATITUDE, MAX_LATITUDE), lon);
+    [x, y]
+}
+
+/// metres on the ground per world unit at the latitude, mercator distances are stretched by
+/// `1 / cos(lat)` so that they are the same in every direction around a point. Clamped like
+/// `world_point` so that the scale does not reach zero at the poles
+fn metres_per_unit(lat: f64) -> f64 {
+    EARTH_CIRCUMFERENCE * lat.clamp(-MAX_LATITUDE, MAX_LATITUDE).to_radians().cos()
+}
+
+/// element indexed by the bounding box of its geometry, nodes by their position
+#[derive(Clone, Debug)]
+struct IndexedElement {
+    element: ElementId,
+    envelope: AABB<WorldPoint>,
+}
+
+impl RTreeObject for IndexedElement {
+    type Envelope = AABB<WorldPoint>;
+
+    fn envelope(&self) -> Self::Envelope {
+        self.envelope
+    }
+}
+
+impl PointDistance for IndexedElement {
+    /// squared distance to the closest point of the bounding box, zero inside of it
+    fn distance_2(&self, point: &WorldPoint) -> f64 {
+        self.envelope.distance_2(point)
+    }
+}
+
+/// R-tree of the tagged elements of a dataset, for spatial queries that do not scan every
+/// element. Ways and relations are indexed by their bounding boxes, relations by those of their
+/// members and nested relations, and nodes by their position. Untagged elements are only parts of
+/// others and are not indexed. Distances are in metres and measured to the bounding boxes, so
+/// that a point inside of the box of a way is at distance zero from it
+pub struct SpatialIndex {
+    tree: RTree<IndexedElement>,
+}
+
+impl From<&Osm> for SpatialIndex {
+    fn from(osm: &Osm) -> Self {
+        SpatialIndex::new(&MemoryDataset::from(osm))
+    }
+}
+
+impl SpatialIndex {
+    /// indexes the elements of the in-memory or the memory-mapped dataset, every way is read
+    /// once and nodes are looked up by id
+    pub fn new<D: Dataset + ?Sized>(dataset: &D) -> Self {
+        let position = |id: u64| dataset.node(id).map(|(lat, lon)| world_point(lat, lon));
+        let mut way_envelopes: HashMap<u64, AABB<WorldPoint>> = HashMap::new();
+        let mut elements: Vec<IndexedElement> = dataset
+            .tagged_nodes()
+            .map(|(id, (lat, lon))| IndexedElement {
+                element: ElementId::Node(id),
+                envelope: AABB::from_point(world_point(lat, lon)),
+            })
+            .collect();
+        for way in dataset.ways() {
+            let points: Vec<WorldPoint> = way
+                .nd
+                .iter()
+                .filter_map(|nd| position(nd.reference))
+                .collect();
+            if points.is_empty() {
+                continue;
+            }
+            let envelope = AABB::from_points(&points);
+            way_envelopes.insert(way.id, envelope);
+            if !way.tags().is_empty() {
+                elements.push(IndexedElement {
+                    element: ElementId::Way(way.id),
+                    envelope,
+                });
+            }
+        }
+
+        let relations = dataset.relations();
+        let id_to_relations: HashMap<u64, Arc<Relation>> = relations
+            .iter()
+            .map(|relation| (relation.id, relation.clone()))
+            .collect();
+        elements.extend(
+            relations
+                .iter()
+                .filter(|relation| !relation.tags().is_empty())
+                .filter_map(|relation| {
+                    let corners: Vec<WorldPoint> = nested_relation_ids(relation, &id_to_relations)
+                        .iter()
+                        .flat_map(|relation_id| &id_to_relations[relation_id].member)
+                        .filter_map(|member| match member.member_type {
+                            MemberType::Node => {
+                                position(member.member_ref).map(|point| vec![point])
+                            }
+                            MemberType::Way => way_envelopes
+                                .get(&member.member_ref)
+                                .map(|envelope| vec![envelope.lower(), envelope.upper()]),
+                            MemberType::Relation => None,
+                        })
+                        .flatten()
+                        .collect();
+                    (!corners.is_empty()).then(|| IndexedElement {
+                        element: ElementId::Relation(relation.id),
+                        envelope: AABB::from_points(&corners),
+                    })
+                }),
+        );
+
+        SpatialIndex {
+            tree: RTree::bulk_load(elements),
+        }
+    }
+
+    /// number of indexed elements
+    pub fn len(&self) -> usize {
+        self.tree.size()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.size() == 0
+    }
+
+    /// elements whose bounding box intersects the bounds, including those on their border
+    pub fn features_in_bbox(&self, bounds: &Bounds) -> Vec<ElementId> {
+        let envelope = AABB::from_corners(
+            world_point(bounds.maxlat, bounds.minlon),
+            world_point(bounds.minlat, bounds.maxlon),
+        );
+        self.tree
+            .locate_in_envelope_intersecting(&envelope)
+            .map(|indexed| indexed.element)
+            .collect()
+    }
+
+    /// elements at most `radius` metres from the location with their distance, closest first
+    pub fn features_within_radius(&self, lat: f64, lon: f64, radius: f64) -> Vec<(ElementId, f64)> {
+        let scale = metres_per_unit(lat);
+        let point = world_point(lat, lon);
+        let mut features: Vec<(ElementId, f64)> = self
+            .tree
+            .locate_within_distance(point, (radius / scale).powi(2))
+            .map(|indexed| (indexed.element, indexed.distance_2(&point).sqrt() * scale))
+            .collect();
+        features.sort_by(|(a, a_distance), (b, b_distance)| {
+            a_distance.total_cmp(b_distance).then(a.cmp(b))
+        });
+        features
+    }
+
+    /// element closest to the location with its distance in metres, the lowest id of those at
+    /// the same distance. `None` when the index is empty
+    pub fn nearest_feature(&self, lat: f64, lon: f64) -> Option<(ElementId, f64)> {
+        let point = world_point(lat, lon);
+        let mut nearest = self.tree.nearest_neighbor_iter_with_distance_2(&point);
+        let (first, distance_2) = nearest.next()?;
+        let element = nearest
+            .take_while(|(_, other)| *other == distance_2)
+            .map(|(indexed, _)| indexed.element)
+            .fold(first.element, Ord::min);
+        Some((element, distance_2.sqrt() * metres_per_unit(lat)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        binary::Header,
+        mapped::{MappedDataset, MappedWriter},
+        stream::XmlReader,
+        Bounds, ElementId, Osm,
+    };
+
+    use super::SpatialIndex;
+
+    const OSM_XML: &str = r#"<osm version="0.6">
+  <node id="1" lat="47.0" lon="28.8"/>
+  <node id="2" lat="47.0" lon="28.81"/>
+  <node id="3" lat="47.01" lon="28.81"/>
+  <node id="4" lat="47.02" lon="28.85"><tag k="amenity" v="cafe"/></node>
+  <node id="5" lat="47.1" lon="28.9"/>
+  <node id="6" lat="47.1" lon="28.91"/>
+  <way id="10"><nd ref="1"/><nd ref="2"/><nd ref="3"/><nd ref="1"/><tag k="leisure" v="park"/></way>
+  <way id="11"><nd ref="5"/><nd ref="6"/></way>
+  <relation id="20">
+    <member type="way" ref="11" role="outer"/>
+    <member type="relation" ref="21" role=""/>
+    <tag k="landuse" v="forest"/>
+  </relation>
+  <relation id="21">
+    <member type="node" ref="4" role="label"/>
+  </relation>
+</osm>"#;
+
+    fn index() -> SpatialIndex {
+        let mut osm = Osm::default();
+        XmlReader::new(OSM_XML.as_bytes()).for_each(|element| osm.push(element.unwrap()));
+        SpatialIndex::from(&osm)
+    }
+
+    #[test]
+    fn query_features() {
+        let index = index();
+        // untagged nodes, the untagged way 11 and relation 21 are only parts of other elements
+        assert_eq!(index.len(), 3);
+
+        let mut features = index.features_in_bbox(&Bounds {
+            minlat: 47.005,
+            minlon: 28.805,
+            maxlat: 47.03,
+            maxlon: 28.86,
+        });
+        features.sort();
+        // the box of relation 20 spans its way and the node of the nested relation
+        assert_eq!(
+            features,
+            vec![
+                ElementId::Node(4),
+                ElementId::Way(10),
+                ElementId::Relation(20)
+            ]
+        );
+        assert_eq!(
+            index.features_in_bbox(&Bounds {
+                minlat: 46.0,
+                minlon: 28.0,
+                maxlat: 46.5,
+                maxlon: 28.5,
+            }),
+            vec![]
+        );
+
+        // on the border of the box of relation 20, 0.01° of longitude east of the cafe
+        let features = index.features_within_radius(47.02, 28.86, 1000.0);
+        let ids: Vec<ElementId> = features.iter().map(|(element, _)| *element).collect();
+        assert_eq!(ids, vec![ElementId::Relation(20), ElementId::Node(4)]);
+        assert_eq!(features[0].1, 0.0);
+        assert!((features[1].1 - 759.0).abs() < 2.0);
+        let ids: Vec<ElementId> = index
+            .features_within_radius(47.02, 28.86, 100.0)
+            .into_iter()
+            .map(|(element, _)| element)
+            .collect();
+        assert_eq!(ids, vec![ElementId::Relation(20)]);
+
+        // 0.005° of longitude east of the park
+        let (nearest, distance) = index.nearest_feature(47.005, 28.815).unwrap();
+        assert_eq!(nearest, ElementId::Way(10));
+        assert!((distance - 380.0).abs() < 2.0);
+        assert!(SpatialIndex::from(&Osm::default())
+            .nearest_feature(47.0, 28.8)
+            .is_none());
+
+        // the poles are at the edges of the projection and not everything is within a radius
+        assert_eq!(index.features_within_radius(90.0, 28.8, 1000.0), vec![]);
+        assert_eq!(index.features_within_radius(-90.0, 28.8, 1000.0), vec![]);
+        let (_, distance) = index.nearest_feature(-90.0, 28.8).unwrap();
+        assert!(distance.is_finite());
+    }
+
+    #[test]
+    fn index_mapped_dataset() {
+        let mut writer = MappedWriter::new();
+        XmlReader::new(OSM_XML.as_bytes()).for_each(|element| writer.write(&element.unwrap()));
+        let bytes = writer.finish(Vec::new(), &Header::new(None)).unwrap();
+        let path =
+            std::env::temp_dir().join(format!("osm-tiles-spatial-{}.map", std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+        let dataset = MappedDataset::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let index = SpatialIndex::new(&dataset);
+        assert_eq!(index.len(), 3);
+        assert_eq!(
+            index
+                .nearest_feature(47.005, 28.815)
+                .map(|(element, _)| element),
+            Some(ElementId::Way(10))
+        );
+    }
+}
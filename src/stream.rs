@@ -0,0 +1,498 @@
+use std::{
+    io::{self, BufRead, Write},
+    str::FromStr,
+};
+
+use ciborium_ll::{Encoder, Header};
+use quick_xml::events::{BytesStart, Event};
+
+use crate::{
+    osc::Action,
+    symbol::{with_symbol_table, SymbolTable},
+    Bounds, Element, Member, Metadata, Nd, Node, Osm, Relation, Tag, Way, GENERATOR, OSM_VERSION,
+};
+
+fn xml_error(error: impl std::error::Error + Send + Sync + 'static) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+fn optional_attribute<T: FromStr>(element: &BytesStart, name: &str) -> io::Result<Option<T>> {
+    let Some(value) = element.try_get_attribute(name).map_err(xml_error)? else {
+        return Ok(None);
+    };
+    let value = value.unescape_value().map_err(xml_error)?;
+    value.parse::<T>().map(Some).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid value {} for attribute {}", value, name),
+        )
+    })
+}
+
+fn attribute<T: FromStr>(element: &BytesStart, name: &str) -> io::Result<T> {
+    optional_attribute(element, name)?.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "missing attribute {} on {}",
+                name,
+                String::from_utf8_lossy(element.name().as_ref())
+            ),
+        )
+    })
+}
+
+/// optional version, timestamp, changeset, user, uid and visible attributes of an element
+fn read_metadata(start: &BytesStart) -> io::Result<Metadata> {
+    Ok(Metadata {
+        version: optional_attribute(start, "version")?,
+        timestamp: optional_attribute(start, "timestamp")?,
+        changeset: optional_attribute(start, "changeset")?,
+        user: optional_attribute(start, "user")?,
+        uid: optional_attribute(start, "uid")?,
+        visible: optional_attribute(start, "visible")?,
+    })
+}
+
+/// source of elements which also exposes the header of the file, available once the first
+/// element has been read
+pub trait ElementReader: Iterator<Item = io::Result<Element>> {
+    fn bounds(&self) -> Option<&Bounds>;
+    fn generator(&self) -> Option<&str>;
+}
+
+impl<R: ElementReader + ?Sized> ElementReader for Box<R> {
+    fn bounds(&self) -> Option<&Bounds> {
+        (**self).bounds()
+    }
+
+    fn generator(&self) -> Option<&str> {
+        (**self).generator()
+    }
+}
+
+/// event driven reader over an OSM XML document, yields one node, way or relation at a time
+/// without keeping the rest of the file in memory. Also reads OsmChange documents, the block of
+/// the last returned element is available through `action`.
+pub struct XmlReader<R: BufRead> {
+    reader: quick_xml::Reader<R>,
+    buffer: Vec<u8>,
+    action: Option<Action>,
+    bounds: Option<Bounds>,
+    generator: Option<String>,
+}
+
+impl<R: BufRead> XmlReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader: quick_xml::Reader::from_reader(reader),
+            buffer: Vec::new(),
+            action: None,
+            bounds: None,
+            generator: None,
+        }
+    }
+
+    /// create, modify or delete block containing the last element, `None` outside of OsmChange
+    /// documents
+    pub fn action(&self) -> Option<Action> {
+        self.action
+    }
+
+    fn read_element(&mut self) -> io::Result<Option<Element>> {
+        let mut current: Option<Element> = None;
+        loop {
+            self.buffer.clear();
+            let (start, is_empty) = match self
+                .reader
+                .read_event_into(&mut self.buffer)
+                .map_err(xml_error)?
+            {
+                Event::Start(start) => (start, false),
+                Event::Empty(start) => (start, true),
+                Event::End(end) => {
+                    match end.name().as_ref() {
+                        b"node" | b"way" | b"relation" if current.is_some() => {
+                            return Ok(current);
+                        }
+                        b"create" | b"modify" | b"delete" => self.action = None,
+                        _ => {}
+                    }
+                    continue;
+                }
+                Event::Eof => {
+                    if current.is_some() {
+                        return Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            "unterminated element at the end of the document",
+                        ));
+                    }
+                    return Ok(None);
+                }
+                _ => continue,
+            };
+
+            match (start.name().as_ref(), current.as_mut()) {
+                (b"osm" | b"osmChange", None) => {
+                    self.generator = optional_attribute(&start, "generator")?;
+                }
+                (b"bounds", None) => {
+                    self.bounds = Some(Bounds {
+                        minlat: attribute(&start, "minlat")?,
+                        minlon: attribute(&start, "minlon")?,
+                        maxlat: attribute(&start, "maxlat")?,
+                        maxlon: attribute(&start, "maxlon")?,
+                    });
+                }
+                (b"create", None) if !is_empty => self.action = Some(Action::Create),
+                (b"modify", None) if !is_empty => self.action = Some(Action::Modify),
+                (b"delete", None) if !is_empty => self.action = Some(Action::Delete),
+                (b"node", _) => {
+                    // deleted nodes in change files don't have to carry their coordinates
+                    let coordinate = |name| match self.action {
+                        Some(Action::Delete) => {
+                            Ok(optional_attribute(&start, name)?.unwrap_or(f64::NAN))
+                        }
+                        _ => attribute(&start, name),
+                    };
+                    current = Some(Element::Node(Node {
+                        id: attribute(&start, "id")?,
+                        lat: coordinate("lat")?,
+                        lon: coordinate("lon")?,
+                        metadata: read_metadata(&start)?,
+                        ..Default::default()
+                    }));
+                }
+                (b"way", _) => {
+                    current = Some(Element::Way(Way {
+                        id: attribute(&start, "id")?,
+                        metadata: read_metadata(&start)?,
+                        ..Default::default()
+                    }));
+                }
+                (b"relation", _) => {
+                    current = Some(Element::Relation(Relation {
+                        id: attribute(&start, "id")?,
+                        metadata: read_metadata(&start)?,
+                        ..Default::default()
+                    }));
+                }
+                (b"tag", Some(element)) => {
+                    element.tag_mut().get_or_insert_with(Vec::new).push(Tag {
+                        k: attribute(&start, "k")?,
+                        v: attribute(&start, "v")?,
+                    });
+                }
+                (b"nd", Some(Element::Way(way))) => {
+                    way.nd.push(Nd {
+                        reference: attribute(&start, "ref")?,
+                    });
+                }
+                (b"member", Some(Element::Relation(relation))) => {
+                    relation.member.push(Member {
+                        member_type: attribute(&start, "type")?,
+                        member_ref: attribute(&start, "ref")?,
+                        role: attribute(&start, "role")?,
+                        tag: None,
+                    });
+                }
+                _ => {}
+            }
+
+            if is_empty {
+                if let b"node" | b"way" | b"relation" = start.name().as_ref() {
+                    return Ok(current);
+                }
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for XmlReader<R> {
+    type Item = io::Result<Element>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_element().transpose()
+    }
+}
+
+impl<R: BufRead> ElementReader for XmlReader<R> {
+    fn bounds(&self) -> Option<&Bounds> {
+        self.bounds.as_ref()
+    }
+
+    fn generator(&self) -> Option<&str> {
+        self.generator.as_deref()
+    }
+}
+
+/// reads elements that are already in memory, like a deserialized osm.bin or merged inputs
+pub struct MemoryReader {
+    elements: Box<dyn Iterator<Item = Element>>,
+    bounds: Option<Bounds>,
+    generator: Option<String>,
+}
+
+impl MemoryReader {
+    pub fn new(
+        elements: impl Iterator<Item = Element> + 'static,
+        bounds: Option<Bounds>,
+        generator: Option<String>,
+    ) -> Self {
+        MemoryReader {
+            elements: Box::new(elements),
+            bounds,
+            generator,
+        }
+    }
+}
+
+impl From<Osm> for MemoryReader {
+    fn from(mut osm: Osm) -> Self {
+        let (bounds, generator) = (osm.bounds.take(), osm.generator.take());
+        MemoryReader::new(osm.into_elements(), bounds, generator)
+    }
+}
+
+impl Iterator for MemoryReader {
+    type Item = io::Result<Element>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.elements.next().map(Ok)
+    }
+}
+
+impl ElementReader for MemoryReader {
+    fn bounds(&self) -> Option<&Bounds> {
+        self.bounds.as_ref()
+    }
+
+    fn generator(&self) -> Option<&str> {
+        self.generator.as_deref()
+    }
+}
+
+/// incrementally writes elements as a CBOR encoded `Osm`, the map and the arrays use indefinite
+/// lengths so nothing has to be buffered. Elements have to arrive grouped by type, as they do in
+/// OSM XML and PBF files. Tags are written as indexes into the string table of the written
+/// elements, which is returned by `finish`
+pub struct CborWriter<W: Write> {
+    writer: W,
+    section: Option<&'static str>,
+    written_sections: Vec<&'static str>,
+    table: SymbolTable,
+}
+
+impl<W: Write> CborWriter<W> {
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        Encoder::from(&mut writer).push(Header::Map(None))?;
+        Ok(Self {
+            writer,
+            section: None,
+            written_sections: Vec::new(),
+            table: SymbolTable::default(),
+        })
+    }
+
+    fn start_section(&mut self, section: &'static str) -> io::Result<()> {
+        if self.section == Some(section) {
+            return Ok(());
+        }
+        if self.written_sections.contains(&section) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} elements are not grouped together", section),
+            ));
+        }
+        let mut encoder = Encoder::from(&mut self.writer);
+        if self.section.is_some() {
+            encoder.push(Header::Break)?;
+        }
+        encoder.text(section, None)?;
+        encoder.push(Header::Array(None))?;
+        self.section = Some(section);
+        self.written_sections.push(section);
+        Ok(())
+    }
+
+    pub fn write(&mut self, element: &Element) -> io::Result<()> {
+        self.start_section(match element {
+            Element::Node(_) => "node",
+            Element::Way(_) => "way",
+            Element::Relation(_) => "relation",
+        })?;
+        let writer = &mut self.writer;
+        with_symbol_table(&mut self.table, || match element {
+            Element::Node(node) => ciborium::into_writer(node, writer),
+            Element::Way(way) => ciborium::into_writer(way, writer),
+            Element::Relation(relation) => ciborium::into_writer(relation, writer),
+        })
+        .map_err(|error| io::Error::other(error.to_string()))
+    }
+
+    /// closes the document, the header of the source file is written last as the map keys can
+    /// come in any order. The string table has to be written before the document
+    pub fn finish(
+        mut self,
+        bounds: Option<&Bounds>,
+        generator: Option<&str>,
+    ) -> io::Result<(W, SymbolTable)> {
+        let mut encoder = Encoder::from(&mut self.writer);
+        if self.section.is_some() {
+            encoder.push(Header::Break)?;
+        }
+        encoder.text("@version", None)?;
+        encoder.text(OSM_VERSION, None)?;
+        if let Some(generator) = generator {
+            encoder.text("@generator", None)?;
+            encoder.text(generator, None)?;
+        }
+        if let Some(bounds) = bounds {
+            encoder.text("bounds", None)?;
+            ciborium::into_writer(bounds, &mut self.writer)
+                .map_err(|error| io::Error::other(error.to_string()))?;
+        }
+        Encoder::from(&mut self.writer).push(Header::Break)?;
+        self.writer.flush()?;
+        Ok((self.writer, self.table))
+    }
+}
+
+/// incrementally writes elements as an OSM XML document
+pub struct XmlWriter<W: Write> {
+    writer: W,
+    buffer: String,
+}
+
+impl<W: Write> XmlWriter<W> {
+    pub fn new(mut writer: W, bounds: Option<&Bounds>) -> io::Result<Self> {
+        write!(
+            writer,
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<osm version=\"{}\" generator=\"{}\">\n",
+            OSM_VERSION, GENERATOR
+        )?;
+        let mut buffer = String::new();
+        if let Some(bounds) = bounds {
+            quick_xml::se::to_writer_with_root(&mut buffer, "bounds", bounds).map_err(xml_error)?;
+            writer.write_all(buffer.as_bytes())?;
+            writer.write_all(b"\n")?;
+        }
+        Ok(Self { writer, buffer })
+    }
+
+    pub fn write(&mut self, element: &Element) -> io::Result<()> {
+        self.buffer.clear();
+        match element {
+            Element::Node(node) => {
+                quick_xml::se::to_writer_with_root(&mut self.buffer, "node", node)
+            }
+            Element::Way(way) => quick_xml::se::to_writer_with_root(&mut self.buffer, "way", way),
+            Element::Relation(relation) => {
+                quick_xml::se::to_writer_with_root(&mut self.buffer, "relation", relation)
+            }
+        }
+        .map_err(xml_error)?;
+        self.writer.write_all(self.buffer.as_bytes())?;
+        self.writer.write_all(b"\n")
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.write_all(b"</osm>\n")?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{symbol::with_string_table, Element, MemberType, Osm};
+
+    use super::{CborWriter, ElementReader, XmlReader, XmlWriter};
+
+    const OSM_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <bounds minlat="46.9" minlon="28.7" maxlat="47.1" maxlon="28.9"/>
+  <node id="1" lat="47.0" lon="28.8" version="3" timestamp="2023-01-01T00:00:00Z" changeset="5" user="mapper" uid="7" visible="true"/>
+  <node id="2" lat="47.1" lon="28.8">
+    <tag k="name" v="Fântână &amp; Co"/>
+  </node>
+  <node id="3" lat="47.1" lon="28.9"/>
+  <way id="10">
+    <nd ref="1"/>
+    <nd ref="2"/>
+    <nd ref="3"/>
+    <nd ref="1"/>
+    <tag k="leisure" v="park"/>
+  </way>
+  <relation id="100">
+    <member type="way" ref="10" role="outer"/>
+    <tag k="type" v="multipolygon"/>
+  </relation>
+</osm>"#;
+
+    #[test]
+    fn stream_xml_to_cbor_and_back() {
+        let mut reader = XmlReader::new(OSM_XML.as_bytes());
+        let elements = reader
+            .by_ref()
+            .collect::<std::io::Result<Vec<Element>>>()
+            .unwrap();
+        assert_eq!(elements.len(), 5);
+
+        let mut writer = CborWriter::new(Vec::new()).unwrap();
+        elements
+            .iter()
+            .for_each(|element| writer.write(element).unwrap());
+        let (bytes, table) = writer.finish(reader.bounds(), reader.generator()).unwrap();
+        // tags are written as indexes into the string table of the written elements
+        assert_eq!(
+            table.strings(),
+            [
+                "name",
+                "Fântână & Co",
+                "leisure",
+                "park",
+                "type",
+                "multipolygon"
+            ]
+        );
+        let strings: Vec<String> = table.strings().iter().map(|s| s.to_string()).collect();
+        let osm: Osm =
+            with_string_table(&strings, || ciborium::from_reader(bytes.as_slice())).unwrap();
+
+        assert_eq!(osm.node.len(), 3);
+        assert_eq!(osm.node[0].metadata.version, Some(3));
+        assert_eq!(osm.node[0].metadata.user.as_deref(), Some("mapper"));
+        assert_eq!(osm.node[0].metadata.visible, Some(true));
+        assert_eq!(osm.node[1].metadata.version, None);
+        assert_eq!(osm.node[1].tag.as_ref().unwrap()[0].v, "Fântână & Co");
+        assert_eq!(osm.generator.as_deref(), Some("test"));
+        assert_eq!(osm.bounds.as_ref().unwrap().maxlon, 28.9);
+        assert_eq!(osm.way[0].nd.len(), 4);
+        assert_eq!(osm.relation[0].member[0].member_ref, 10);
+
+        let mut writer = XmlWriter::new(Vec::new(), reader.bounds()).unwrap();
+        elements
+            .iter()
+            .for_each(|element| writer.write(element).unwrap());
+        let xml = writer.finish().unwrap();
+        let mut reader = XmlReader::new(xml.as_slice());
+        let mut osm = Osm::default();
+        reader
+            .by_ref()
+            .for_each(|element| osm.push(element.unwrap()));
+        assert_eq!(osm.node.len(), 3);
+        let Element::Node(node) = &elements[0] else {
+            panic!("expected a node");
+        };
+        assert_eq!(osm.node[0].metadata, node.metadata);
+        assert_eq!(
+            osm.node[0].metadata.timestamp.as_deref(),
+            Some("2023-01-01T00:00:00Z")
+        );
+        assert_eq!(osm.node[0].metadata.changeset, Some(5));
+        assert_eq!(reader.bounds().unwrap().minlat, 46.9);
+        assert_eq!(osm.relation[0].member[0].role, "outer");
+        assert_eq!(osm.relation[0].member[0].member_type, MemberType::Way);
+    }
+}
@@ -0,0 +1,398 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    convert::Infallible,
+    fmt,
+    ops::Deref,
+    str::FromStr,
+    sync::{OnceLock, RwLock},
+};
+
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+
+/// interned string, tag keys and values repeat across millions of elements and are stored once.
+/// Comparing symbols compares integers. Interned strings live until the process exits, in an
+/// append-only table that grows by the bytes of each distinct string
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+/// interned at start up so that the symbols below have fixed indexes
+const PREDEFINED: [&str; 14] = [
+    "name",
+    "yes",
+    "area",
+    "building",
+    "leisure",
+    "park",
+    "landuse",
+    "forest",
+    "natural",
+    "water",
+    "waterway",
+    "addr:housenumber",
+    "highway",
+    "railway",
+];
+
+impl Symbol {
+    pub const NAME: Symbol = Symbol(0);
+    pub const YES: Symbol = Symbol(1);
+    pub const AREA: Symbol = Symbol(2);
+    pub const BUILDING: Symbol = Symbol(3);
+    pub const LEISURE: Symbol = Symbol(4);
+    pub const PARK: Symbol = Symbol(5);
+    pub const LANDUSE: Symbol = Symbol(6);
+    pub const FOREST: Symbol = Symbol(7);
+    pub const NATURAL: Symbol = Symbol(8);
+    pub const WATER: Symbol = Symbol(9);
+    pub const WATERWAY: Symbol = Symbol(10);
+    pub const ADDR_HOUSENUMBER: Symbol = Symbol(11);
+    pub const HIGHWAY: Symbol = Symbol(12);
+    pub const RAILWAY: Symbol = Symbol(13);
+}
+
+/// strings in the first segment of the string table, each next segment holds twice as many
+const FIRST_SEGMENT: usize = 1024;
+/// enough segments for every `u32` symbol
+const SEGMENTS: usize = 23;
+/// strings are copied into chunks of this size, longer strings get a chunk of their own
+const CHUNK: usize = 64 * 1024;
+
+/// segment and position in the segment of the string of a symbol
+fn location(id: u32) -> (usize, usize) {
+    let position = id as usize / FIRST_SEGMENT + 1;
+    let segment = position.ilog2() as usize;
+    (segment, id as usize - ((1 << segment) - 1) * FIRST_SEGMENT)
+}
+
+/// append-only string table. Strings are copied into large chunks that are never freed, so
+/// repeated values cost nothing and new ones only their bytes. The table is read without a lock,
+/// a symbol's string is set before the symbol is handed out and never changes
+struct Interner {
+    segments: [OnceLock<Box<[OnceLock<&'static str>]>>; SEGMENTS],
+    index: RwLock<Index>,
+}
+
+/// lookup of interned strings, locked for writing while a string is added
+#[derive(Default)]
+struct Index {
+    ids: HashMap<&'static str, u32>,
+    /// unused end of the last chunk
+    free: &'static mut [u8],
+}
+
+impl Interner {
+    fn intern(&self, index: &mut Index, value: &str) -> u32 {
+        if let Some(&id) = index.ids.get(value) {
+            return id;
+        }
+        if value.len() > index.free.len() {
+            index.free = Box::leak(vec![0; CHUNK.max(value.len())].into_boxed_slice());
+        }
+        let (bytes, free) = std::mem::take(&mut index.free).split_at_mut(value.len());
+        bytes.copy_from_slice(value.as_bytes());
+        index.free = free;
+        let bytes: &'static [u8] = bytes;
+        let value = std::str::from_utf8(bytes).expect("copied from a string");
+
+        let id = u32::try_from(index.ids.len()).expect("too many interned strings");
+        let (segment, position) = location(id);
+        self.segments[segment].get_or_init(|| {
+            (0..FIRST_SEGMENT << segment)
+                .map(|_| OnceLock::new())
+                .collect()
+        })[position]
+            .set(value)
+            .expect("symbols are added once");
+        index.ids.insert(value, id);
+        id
+    }
+
+    fn string(&self, id: u32) -> &'static str {
+        let (segment, position) = location(id);
+        self.segments[segment]
+            .get()
+            .and_then(|strings| strings[position].get())
+            .expect("symbols are only created for interned strings")
+    }
+}
+
+fn interner() -> &'static Interner {
+    static INTERNER: OnceLock<Interner> = OnceLock::new();
+    INTERNER.get_or_init(|| {
+        let interner = Interner {
+            segments: [const { OnceLock::new() }; SEGMENTS],
+            index: RwLock::default(),
+        };
+        let mut index = Index::default();
+        PREDEFINED.iter().for_each(|value| {
+            interner.intern(&mut index, value);
+        });
+        *interner.index.write().unwrap() = index;
+        interner
+    })
+}
+
+impl Symbol {
+    pub fn new(value: &str) -> Self {
+        if let Some(symbol) = Symbol::get(value) {
+            return symbol;
+        }
+        let interner = interner();
+        Symbol(interner.intern(&mut interner.index.write().unwrap(), value))
+    }
+
+    /// symbol of an already interned string, a string that was never interned is not the key
+    /// or value of any tag
+    pub fn get(value: &str) -> Option<Self> {
+        interner()
+            .index
+            .read()
+            .unwrap()
+            .ids
+            .get(value)
+            .copied()
+            .map(Symbol)
+    }
+
+    /// the interned string, read without taking a lock
+    pub fn as_str(&self) -> &'static str {
+        interner().string(self.0)
+    }
+}
+
+/// string table of a dataset being written, the strings of the symbols serialized with it. A
+/// symbol is added the first time it is serialized, its index is its position in the table
+#[derive(Default)]
+pub struct SymbolTable {
+    indexes: HashMap<Symbol, u32>,
+    strings: Vec<&'static str>,
+}
+
+impl SymbolTable {
+    fn index(&mut self, symbol: Symbol) -> u32 {
+        *self.indexes.entry(symbol).or_insert_with(|| {
+            self.strings.push(symbol.as_str());
+            (self.strings.len() - 1) as u32
+        })
+    }
+
+    /// the strings in the order of their indexes
+    pub fn strings(&self) -> &[&'static str] {
+        &self.strings
+    }
+}
+
+thread_local! {
+    static SYMBOL_TABLE: RefCell<Option<SymbolTable>> = const { RefCell::new(None) };
+    static STRING_TABLE: RefCell<Option<Vec<Symbol>>> = const { RefCell::new(None) };
+}
+
+/// serializes with the string table of a dataset. Binary formats store symbols as indexes into
+/// the table, the symbols serialized while `serialize` runs are added to it
+pub fn with_symbol_table<T>(table: &mut SymbolTable, serialize: impl FnOnce() -> T) -> T {
+    let previous = SYMBOL_TABLE.with(|current| current.replace(Some(std::mem::take(table))));
+    let result = serialize();
+    *table = SYMBOL_TABLE
+        .with(|current| current.replace(previous))
+        .expect("the table is set while serializing");
+    result
+}
+
+/// deserializes with the string table of a dataset. Binary formats store symbols as indexes into
+/// the table written by `with_symbol_table`, they are resolved against it while `deserialize`
+/// runs
+pub fn with_string_table<T>(table: &[String], deserialize: impl FnOnce() -> T) -> T {
+    let table = table.iter().map(|value| Symbol::new(value)).collect();
+    let previous = STRING_TABLE.with(|current| current.replace(Some(table)));
+    let result = deserialize();
+    STRING_TABLE.with(|current| current.replace(previous));
+    result
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(value: &str) -> Self {
+        Symbol::new(value)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(value: String) -> Self {
+        Symbol::new(&value)
+    }
+}
+
+impl FromStr for Symbol {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Symbol::new(s))
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+/// string or symbol a tag is looked up by
+pub trait ToSymbol {
+    /// `None` for strings that were never interned
+    fn to_symbol(&self) -> Option<Symbol>;
+}
+
+impl ToSymbol for Symbol {
+    fn to_symbol(&self) -> Option<Symbol> {
+        Some(*self)
+    }
+}
+
+impl ToSymbol for str {
+    fn to_symbol(&self) -> Option<Symbol> {
+        Symbol::get(self)
+    }
+}
+
+impl ToSymbol for String {
+    fn to_symbol(&self) -> Option<Symbol> {
+        Symbol::get(self)
+    }
+}
+
+impl<T: ToSymbol + ?Sized> ToSymbol for &T {
+    fn to_symbol(&self) -> Option<Symbol> {
+        (**self).to_symbol()
+    }
+}
+
+impl Serialize for Symbol {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            return serializer.serialize_str(self.as_str());
+        }
+        match SYMBOL_TABLE.with(|table| table.borrow_mut().as_mut().map(|table| table.index(*self)))
+        {
+            Some(index) => serializer.serialize_u32(index),
+            None => Err(ser::Error::custom("symbol without a string table")),
+        }
+    }
+}
+
+struct SymbolVisitor;
+
+impl de::Visitor<'_> for SymbolVisitor {
+    type Value = Symbol;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a string or an index into the string table")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Symbol, E> {
+        Ok(Symbol::new(value))
+    }
+
+    fn visit_u64<E: de::Error>(self, index: u64) -> Result<Symbol, E> {
+        STRING_TABLE.with(|table| match &*table.borrow() {
+            Some(table) => usize::try_from(index)
+                .ok()
+                .and_then(|index| table.get(index))
+                .copied()
+                .ok_or_else(|| E::custom(format!("string index out of range {}", index))),
+            None => Err(E::custom("string index without a string table")),
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Symbol {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // datasets written before strings were interned store them inline
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(SymbolVisitor)
+        } else {
+            deserializer.deserialize_any(SymbolVisitor)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        location, with_string_table, with_symbol_table, Symbol, SymbolTable, PREDEFINED, SEGMENTS,
+    };
+
+    #[test]
+    fn intern_strings() {
+        for (index, value) in PREDEFINED.iter().enumerate() {
+            assert_eq!(Symbol::new(value), Symbol(index as u32));
+        }
+        assert_eq!(Symbol::BUILDING.as_str(), "building");
+        assert_eq!(Symbol::new("highway"), Symbol::new("highway"));
+        assert_eq!(Symbol::new("highway"), "highway");
+        assert_eq!(Symbol::get("never interned in any test"), None);
+
+        assert_eq!(location(1023), (0, 1023));
+        assert_eq!(location(1024), (1, 0));
+        assert_eq!(location(3072), (2, 0));
+        assert!(location(u32::MAX).0 < SEGMENTS);
+        // strings past the first segments of the table, read from another thread
+        let values: Vec<String> = (0..5000).map(|index| format!("value {}", index)).collect();
+        let symbols: Vec<Symbol> = values.iter().map(|value| Symbol::new(value)).collect();
+        std::thread::spawn(move || {
+            symbols
+                .iter()
+                .zip(&values)
+                .for_each(|(symbol, value)| assert_eq!(symbol.as_str(), value));
+        })
+        .join()
+        .unwrap();
+
+        let mut bytes = Vec::new();
+        let written = vec![
+            Symbol::new("residential"),
+            Symbol::YES,
+            Symbol::new("residential"),
+        ];
+        assert!(ciborium::into_writer(&written, &mut bytes).is_err());
+        bytes.clear();
+        let mut table = SymbolTable::default();
+        with_symbol_table(&mut table, || ciborium::into_writer(&written, &mut bytes)).unwrap();
+        // only the strings of the written symbols are in the table
+        assert_eq!(table.strings(), ["residential", "yes"]);
+        assert!(ciborium::from_reader::<Vec<Symbol>, _>(bytes.as_slice()).is_err());
+        let table: Vec<String> = table
+            .strings()
+            .iter()
+            .map(|value| value.to_string())
+            .collect();
+        let symbols: Vec<Symbol> =
+            with_string_table(&table, || ciborium::from_reader(bytes.as_slice()).unwrap());
+        assert_eq!(symbols, written);
+    }
+}
@@ -0,0 +1,94 @@
+use crate::{symbol::ToSymbol, Member, Node, Relation, Symbol, Tag, Way};
+
+/// read access to the tags of an element. Keys and values are looked up by string or by
+/// `Symbol`, the tags are compared as symbols
+pub trait Tagged {
+    /// all the tags, empty when the element has none
+    fn tags(&self) -> &[Tag];
+
+    /// value of the first tag with the key
+    fn tag(&self, key: impl ToSymbol) -> Option<Symbol> {
+        let key = key.to_symbol()?;
+        self.tags().iter().find(|tag| tag.k == key).map(|tag| tag.v)
+    }
+
+    fn has_key(&self, key: impl ToSymbol) -> bool {
+        self.tag(key).is_some()
+    }
+
+    fn has_tag(&self, key: impl ToSymbol, value: impl ToSymbol) -> bool {
+        let (Some(key), Some(value)) = (key.to_symbol(), value.to_symbol()) else {
+            return false;
+        };
+        self.tags().iter().any(|tag| tag.k == key && tag.v == value)
+    }
+
+    /// `name:<language>` of the first language in order of preference that is tagged, `name`
+    /// when none is
+    fn name(&self, languages: &[&str]) -> Option<Symbol> {
+        languages
+            .iter()
+            .find_map(|language| self.tag(format!("name:{}", language)))
+            .or_else(|| self.tag(Symbol::NAME))
+    }
+}
+
+impl Tagged for Node {
+    fn tags(&self) -> &[Tag] {
+        self.tag.as_deref().unwrap_or_default()
+    }
+}
+
+impl Tagged for Way {
+    fn tags(&self) -> &[Tag] {
+        self.tag.as_deref().unwrap_or_default()
+    }
+}
+
+impl Tagged for Relation {
+    fn tags(&self) -> &[Tag] {
+        self.tag.as_deref().unwrap_or_default()
+    }
+}
+
+impl Tagged for Member {
+    fn tags(&self) -> &[Tag] {
+        self.tag.as_deref().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Symbol, Tag, Way};
+
+    use super::Tagged;
+
+    fn tag(k: &str, v: &str) -> Tag {
+        Tag {
+            k: k.into(),
+            v: v.into(),
+        }
+    }
+
+    #[test]
+    fn look_up_tags() {
+        let way = Way {
+            tag: Some(vec![
+                tag("building", "yes"),
+                tag("name", "Casa Albă"),
+                tag("name:en", "White House"),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(way.tag("building"), Some(Symbol::YES));
+        assert!(way.has_key("building"));
+        assert!(way.has_tag("building", "yes"));
+        assert!(!way.has_tag("building", "no"));
+        assert_eq!(way.name(&["ru", "en"]).as_deref(), Some("White House"));
+        assert_eq!(way.name(&["ru"]).as_deref(), Some("Casa Albă"));
+
+        let way = Way::default();
+        assert!(way.tags().is_empty());
+        assert_eq!(way.name(&[]), None);
+    }
+}
@@ -7,7 +7,11 @@ use std::{
 use cairo::Context;
 use log::debug;
 
-use crate::{LoopWithType, Osm, Relation, Tag, Type, Way, TILE_SIZE};
+use crate::{
+    classes::{registry, Color, Style},
+    geometry::assemble_rings,
+    Class, Filter, LoopWithType, Member, MemberType, Osm, Relation, Result, Way, TILE_SIZE,
+};
 
 pub fn convert_to_tile(lat: f64, lon: f64) -> (f64, f64) {
     let (lat_rad, lon_rad) = (lat.to_radians(), lon.to_radians());
@@ -22,43 +26,44 @@ pub fn convert_to_int_tile(lat: f64, lon: f64) -> (i32, i32) {
     let tile_y = (lon / TILE_SIZE as f64) as i32;
     (tile_x, tile_y)
 }
-pub fn filter_relations(
-    osm: &Osm,
-    filter: &HashMap<String, HashSet<String>>,
-) -> Vec<Arc<Relation>> {
+/// tiles (at the given zoom) on which a node with the given pixel coordinates has to be rendered,
+/// from zoom 16 on the surrounding tiles are included as well so that features crossing the tile
+/// border are not cut
+pub fn tiles_for_pixel(x: f64, y: f64, zoom: u8) -> Vec<(i32, i32)> {
+    let sorrund_tiles_window = [1, 1, 0, 1, -1, -1, 0, -1, 1];
+
+    let tile = convert_to_int_tile(x, y);
+    let mut tiles = vec![tile];
+    if zoom > 15 {
+        sorrund_tiles_window.windows(2).for_each(|sliding_window| {
+            tiles.push((tile.0 + sliding_window[0], tile.1 + sliding_window[1]));
+        })
+    }
+    tiles
+}
+
+pub fn filter_relations(osm: &Osm, filter: &Filter) -> Vec<Arc<Relation>> {
     osm.relation
         .iter()
-        .filter(|&relation| {
-            if let Some(tag) = &relation.tag {
-                tag.iter()
-                    .filter(|item| {
-                        filter.get(&item.k).is_some()
-                            && filter.get(&item.k).unwrap().contains(&item.v)
-                    })
-                    .count()
-                    .eq(&filter.len())
-            } else {
-                false
-            }
-        })
+        .filter(|&relation| relation_matches(relation, filter))
         .cloned()
         .collect()
 }
 
+pub fn relation_matches(relation: &Relation, filter: &Filter) -> bool {
+    filter.matches(MemberType::Relation, relation)
+}
+
 pub fn filter_ways_from_relations(osm: &Osm, relations: &[Arc<Relation>]) -> Vec<Arc<Way>> {
-    let ways_to_filter: HashSet<u64> =
-        relations
-            .iter()
-            .fold(HashSet::<u64>::new(), |mut acc, relation| {
-                relation
-                    .member
-                    .iter()
-                    .filter(|member| member.member_type.eq("way"))
-                    .for_each(|way| {
-                        acc.insert(way.member_ref);
-                    });
-                acc
-            });
+    let id_to_relations = osm
+        .relation
+        .iter()
+        .map(|relation| (relation.id, relation.clone()))
+        .collect();
+    let ways_to_filter = way_ids_from_relations(
+        relations.iter().map(|relation| relation.as_ref()),
+        &id_to_relations,
+    );
 
     osm.way
         .iter()
@@ -67,204 +72,329 @@ pub fn filter_ways_from_relations(osm: &Osm, relations: &[Arc<Relation>]) -> Vec
         .collect()
 }
 
-pub fn create_filter_expression() -> HashMap<String, HashSet<String>> {
-    let mut filters = HashMap::<String, HashSet<String>>::new();
-    filters.insert(
-        "leisure".to_string(),
-        HashSet::from_iter(
-            vec!["park"]
-                .iter()
-                .map(|item| item.to_string())
-                .collect::<Vec<String>>(),
-        ),
-    );
-    filters
+pub fn way_ids_from_relations<'a>(
+    relations: impl Iterator<Item = &'a Relation>,
+    id_to_relations: &HashMap<u64, Arc<Relation>>,
+) -> HashSet<u64> {
+    relations.fold(HashSet::<u64>::new(), |mut acc, relation| {
+        acc.extend(relation_way_ids(relation, id_to_relations));
+        acc
+    })
 }
 
-pub fn extract_loops_to_render(
-    relation: &Relation,
-    id_to_ways: &HashMap<u64, Arc<Way>>,
-) -> Vec<LoopWithType> {
-    let ways: Vec<&Arc<Way>> = relation
+fn collect_members<'a>(
+    relation: &'a Relation,
+    id_to_relations: &'a HashMap<u64, Arc<Relation>>,
+    visited: &mut HashSet<u64>,
+    members: &mut Vec<&'a Member>,
+) {
+    if !visited.insert(relation.id) {
+        return;
+    }
+    relation
         .member
         .iter()
-        .flat_map(|member| id_to_ways.get(&member.member_ref))
-        .collect();
-
-    let mut ways_to_visit = ways.iter().fold(HashSet::<u64>::new(), |mut acc, way| {
-        acc.insert(way.id);
-        acc
-    });
-
-    let segments = ways
-        .iter()
-        .fold(HashMap::<u64, HashSet<u64>>::new(), |mut acc, way| {
-            acc.entry(way.nd.first().unwrap().reference)
-                .or_default()
-                .insert(way.id);
-
-            acc.entry(way.nd.last().unwrap().reference)
-                .or_default()
-                .insert(way.id);
-            acc
+        .for_each(|member| match member.member_type {
+            MemberType::Relation => {
+                if let Some(nested) = id_to_relations.get(&member.member_ref) {
+                    collect_members(nested, id_to_relations, visited, members);
+                }
+            }
+            _ => members.push(member),
         });
+}
 
-    segments.iter().for_each(|(key, value)| {
-        debug!("segments for {} {:?}", key, value);
-    });
-
-    let mut loops = Vec::<LoopWithType>::new();
-
-    let a = ways.first().unwrap();
-    loops.push(LoopWithType::new_with_type(a.id, check_way_type(a)));
-
-    loops
-        .last_mut()
-        .unwrap()
-        .memeber_loop
-        .extend(a.nd.iter().map(|nd| nd.reference));
-    ways_to_visit.remove(&a.id);
+/// node and way members of the relation, members of nested relations (super-relations) are
+/// resolved recursively. Every relation is expanded once so cyclic memberships terminate
+pub fn resolve_members<'a>(
+    relation: &'a Relation,
+    id_to_relations: &'a HashMap<u64, Arc<Relation>>,
+) -> Vec<&'a Member> {
+    let mut members = Vec::new();
+    collect_members(relation, id_to_relations, &mut HashSet::new(), &mut members);
+    members
+}
 
-    while !ways_to_visit.is_empty() {
-        debug!(
-            "try to find next segment for {}",
-            loops.last().unwrap().memeber_loop.last().unwrap()
-        );
-        let next_way = if let Some(next_way_to_process) = segments
-            .get(loops.last().unwrap().memeber_loop.last().unwrap())
-            .unwrap()
-            .iter()
-            .filter(|item| ways_to_visit.contains(item))
-            .last()
-        {
-            let b = id_to_ways.get(next_way_to_process).unwrap();
+/// ids of the relation itself and of all the relations nested in it
+pub fn nested_relation_ids(
+    relation: &Relation,
+    id_to_relations: &HashMap<u64, Arc<Relation>>,
+) -> HashSet<u64> {
+    let mut visited = HashSet::new();
+    collect_members(relation, id_to_relations, &mut visited, &mut Vec::new());
+    visited
+}
 
-            // check if we need to invert the sgment in order to mach the start of the new
-            // segment with the end of the previous
-            if loops.last().unwrap().memeber_loop.last().unwrap().eq(&b
-                .nd
-                .first()
-                .unwrap()
-                .reference)
-            {
-                loops
-                    .last_mut()
-                    .unwrap()
-                    .memeber_loop
-                    .extend(b.nd.iter().map(|nd| nd.reference));
-            } else {
-                loops
-                    .last_mut()
-                    .unwrap()
-                    .memeber_loop
-                    .extend(b.nd.iter().rev().map(|nd| nd.reference));
-            };
-            next_way_to_process
-        } else {
-            debug!("processing new loop");
-            debug!("pick any out of {}", ways_to_visit.len());
-            let pick_new_way = ways_to_visit.iter().next().unwrap();
-            let a = id_to_ways.get(pick_new_way).unwrap();
+/// ids of the ways of the relation and of its nested relations, in member order without duplicates
+pub fn relation_way_ids(
+    relation: &Relation,
+    id_to_relations: &HashMap<u64, Arc<Relation>>,
+) -> Vec<u64> {
+    let mut seen = HashSet::new();
+    resolve_members(relation, id_to_relations)
+        .into_iter()
+        .filter(|member| member.member_type == MemberType::Way)
+        .map(|member| member.member_ref)
+        .filter(|way_id| seen.insert(*way_id))
+        .collect()
+}
 
-            loops.push(LoopWithType::new_with_type(a.id, check_way_type(a)));
+pub fn create_filter_expression() -> Filter {
+    "relation[leisure=park]".parse().unwrap()
+}
 
-            loops
-                .last_mut()
-                .unwrap()
-                .memeber_loop
-                .extend(a.nd.iter().map(|nd| nd.reference));
-            &a.id
+/// rings of the relation and of its nested relations. Ways with the `inner` role are assembled
+/// into the holes, the other ways into the outer rings. Ways missing from `id_to_ways` are
+/// skipped, rings that can not be closed are returned open
+pub fn extract_loops_to_render(
+    relation: &Relation,
+    id_to_ways: &HashMap<u64, Arc<Way>>,
+    id_to_relations: &HashMap<u64, Arc<Relation>>,
+) -> Vec<LoopWithType> {
+    let mut seen = HashSet::new();
+    let (mut outer, mut inner) = (Vec::<&Way>::new(), Vec::<&Way>::new());
+    for member in resolve_members(relation, id_to_relations) {
+        if member.member_type != MemberType::Way || !seen.insert(member.member_ref) {
+            continue;
+        }
+        let Some(way) = id_to_ways.get(&member.member_ref) else {
+            debug!(
+                "relation {} references missing way {}",
+                relation.id, member.member_ref
+            );
+            continue;
         };
-
-        ways_to_visit.remove(next_way);
+        if member.role == "inner" {
+            inner.push(way);
+        } else {
+            outer.push(way);
+        }
     }
 
-    loops.iter().for_each(|ordered_nodes| {
-        debug!("nodes in order to render");
-        ordered_nodes.memeber_loop.iter().for_each(|node| {
-            debug!("node {}", node);
-        });
-    });
+    let mut loops = Vec::new();
+    for (ways, inner) in [(outer, false), (inner, true)] {
+        for ring in assemble_rings(ways) {
+            let way_id = ring.ways[0];
+            let closed = ring.is_closed();
+            if !closed {
+                debug!(
+                    "relation {} has an unclosed ring starting with way {}",
+                    relation.id, way_id
+                );
+            }
+            loops.push(LoopWithType {
+                memeber_loop: ring.nodes,
+                way_id: Some(way_id),
+                inner,
+                closed,
+            });
+        }
+    }
     loops
 }
 
-pub fn check_relation_type(relation: &Relation) -> Type {
-    if let Some(tag) = &relation.tag {
-        return check_tag_type(tag);
+/// adds the loop to the current path, in pixels relative to the tile origin. Closed loops are
+/// closed sub-paths so that fills with the even-odd rule cut holes for the inner rings
+pub fn trace_loop(
+    ring: &LoopWithType,
+    context: &Context,
+    mapped_nodes: &HashMap<u64, (f64, f64)>,
+    min_x: f64,
+    min_y: f64,
+) {
+    ring.memeber_loop
+        .iter()
+        .flat_map(|node| mapped_nodes.get(node))
+        .enumerate()
+        .for_each(|(index, (x, y))| {
+            if index == 0 {
+                context.move_to(x - min_x, y - min_y);
+            } else {
+                context.line_to(x - min_x, y - min_y);
+            }
+        });
+    if ring.closed {
+        context.close_path();
     }
+}
 
-    Type::Generic
+/// classes of the relation in the registry, in drawing order
+pub fn relation_classes(relation: &Relation) -> Vec<Class> {
+    registry().classify(relation)
 }
-pub fn check_way_type(way: &Way) -> Type {
-    if let Some(tag) = &way.tag {
-        return check_tag_type(tag);
-    }
-    Type::Generic
+
+/// classes of the way in the registry, in drawing order
+pub fn way_classes(way: &Way) -> Vec<Class> {
+    registry().classify(way)
 }
 
-fn check_tag_type(tag: &[Tag]) -> Type {
-    if tag.iter().any(|t| t.k.eq("leisure") && t.v.eq("park")) {
-        return Type::Park;
-    } else if tag.iter().any(|t| t.k.eq("landuse") && t.v.eq("forest")) {
-        return Type::Forest;
-    } else if tag.iter().any(|t| t.k.eq("building")) {
-        return Type::Building;
-    } else if tag.iter().any(|t| t.k.eq("natural") && t.v.eq("water")) {
-        return Type::Water;
-    } else if tag.iter().any(|t| t.k.eq("waterway")) {
-        return Type::WaterRiver;
-    };
-    Type::Generic
+pub fn set_color(context: &Context, color: Color) {
+    context.set_source_rgba(color.red, color.green, color.blue, color.alpha);
 }
 
-pub fn set_context_for_type(way_type: &Type, context: &Context) {
-    context.set_line_width(1f64);
-    match *way_type {
-        Type::Water | Type::WaterRiver => {
-            context.set_source_rgb(0.286, 0.402, 0.510);
-            context.set_line_width(3f64);
-        }
-        Type::Park => {
-            context.set_source_rgb(0.442, 0.640, 0.551);
-        }
-        Type::Forest => {
-            context.set_source_rgb(0.269, 0.480, 0.385);
+/// sets the colour and width of the line, or of its casing. Areas without a stroke colour are
+/// outlined in their fill colour
+pub fn set_context_for_line(style: &Style, z: i32, casing: bool, context: &Context) {
+    let width = style.line_width(z);
+    match (casing, style.casing) {
+        (true, Some(color)) => {
+            set_color(context, color);
+            context.set_line_width(width + 2.0);
         }
-        Type::Building => {
-            context.set_line_width(1f64);
-            context.set_source_rgba(0.5, 0.5, 0.5, 0.2);
-        }
-        Type::Generic => {
-            context.set_source_rgb(0.5, 0.5, 0.5);
+        _ => {
+            if let Some(color) = style.stroke.or(style.fill) {
+                set_color(context, color);
+            }
+            context.set_line_width(width);
         }
     }
 }
 
-pub fn end_context_for_way_type(way_type: &Type, context: &Context) {
-    end_context_for_type(way_type, context, false);
+/// strokes the current path, dashed lines get white dashes over the line. Classes without a
+/// stroke or fill colour are not drawn
+pub fn stroke_line(style: &Style, z: i32, context: &Context) -> Result<()> {
+    if style.stroke.or(style.fill).is_none() {
+        context.new_path();
+        return Ok(());
+    }
+    set_context_for_line(style, z, false, context);
+    if style.dashed {
+        context.stroke_preserve()?;
+        let width = style.line_width(z) / 2.0;
+        context.set_source_rgb(1.0, 1.0, 1.0);
+        context.set_line_width(width);
+        context.set_line_cap(cairo::LineCap::Butt);
+        context.set_dash(&[width * 4.0], 0.0);
+        context.stroke()?;
+        context.set_dash(&[], 0.0);
+        context.set_line_cap(cairo::LineCap::Round);
+    } else {
+        context.stroke()?;
+    }
+    Ok(())
 }
 
-pub fn end_context_for_relation_type(relation_type: &Type, context: &Context) {
-    end_context_for_type(relation_type, context, true);
-}
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, sync::Arc};
 
-fn end_context_for_type(way_type: &Type, context: &Context, is_relation: bool) {
-    match *way_type {
-        Type::Water => {
-            context.fill().unwrap();
-        }
-        Type::WaterRiver => {}
-        Type::Park => {
-            context.fill().unwrap();
-        }
-        Type::Forest => {
-            context.fill().unwrap();
-        }
-        Type::Building => {
-            if !is_relation {
-                context.fill().unwrap();
-            }
-        }
-        Type::Generic => {}
+    use crate::{Member, MemberType, Nd, Relation, Way};
+
+    use super::{extract_loops_to_render, nested_relation_ids, relation_way_ids};
+
+    fn relation(id: u64, members: &[(MemberType, u64)]) -> (u64, Arc<Relation>) {
+        let member = members
+            .iter()
+            .map(|(member_type, member_ref)| Member {
+                member_type: *member_type,
+                member_ref: *member_ref,
+                role: String::new(),
+                tag: None,
+            })
+            .collect();
+        (
+            id,
+            Arc::new(Relation {
+                id,
+                member,
+                ..Default::default()
+            }),
+        )
+    }
+
+    #[test]
+    fn resolve_nested_relations() {
+        // node 10 shares its id with way 10, relation 2 nests relation 3 which nests relation 2
+        // again
+        let id_to_relations: HashMap<u64, Arc<Relation>> = [
+            relation(
+                1,
+                &[
+                    (MemberType::Node, 10),
+                    (MemberType::Way, 10),
+                    (MemberType::Relation, 2),
+                ],
+            ),
+            relation(2, &[(MemberType::Way, 20), (MemberType::Relation, 3)]),
+            relation(
+                3,
+                &[
+                    (MemberType::Way, 30),
+                    (MemberType::Way, 10),
+                    (MemberType::Relation, 2),
+                ],
+            ),
+        ]
+        .into_iter()
+        .collect();
+
+        let root = id_to_relations.get(&1).unwrap();
+        assert_eq!(relation_way_ids(root, &id_to_relations), vec![10, 20, 30]);
+        assert_eq!(
+            relation_way_ids(id_to_relations.get(&2).unwrap(), &id_to_relations),
+            vec![20, 30, 10]
+        );
+        let mut nested: Vec<u64> = nested_relation_ids(root, &id_to_relations)
+            .into_iter()
+            .collect();
+        nested.sort();
+        assert_eq!(nested, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn assemble_rings_by_role() {
+        // a lake drawn with two outer ways, an island and a dangling inner way
+        let way = |id: u64, nodes: &[u64]| {
+            let way = Way {
+                id,
+                nd: nodes.iter().map(|&reference| Nd { reference }).collect(),
+                ..Default::default()
+            };
+            (id, Arc::new(way))
+        };
+        let id_to_ways: HashMap<u64, Arc<Way>> = [
+            way(1, &[1, 2, 3]),
+            way(2, &[3, 4, 1]),
+            way(3, &[5, 6, 7, 5]),
+            way(4, &[8, 9]),
+        ]
+        .into_iter()
+        .collect();
+        let mut lake = relation(10, &[(MemberType::Way, 3), (MemberType::Way, 4)]).1;
+        Arc::make_mut(&mut lake)
+            .member
+            .iter_mut()
+            .for_each(|member| member.role = "inner".to_string());
+        Arc::make_mut(&mut lake).member.extend(
+            relation(
+                0,
+                &[
+                    (MemberType::Way, 1),
+                    (MemberType::Way, 2),
+                    (MemberType::Way, 5),
+                ],
+            )
+            .1
+            .member
+            .clone(),
+        );
+
+        // way 5 is not in the dataset and is skipped
+        let loops = extract_loops_to_render(&lake, &id_to_ways, &HashMap::new());
+        let rings: Vec<(Vec<u64>, bool, bool)> = loops
+            .into_iter()
+            .map(|ring| (ring.memeber_loop, ring.inner, ring.closed))
+            .collect();
+        assert_eq!(
+            rings,
+            vec![
+                (vec![1, 2, 3, 4, 1], false, true),
+                (vec![5, 6, 7, 5], true, true),
+                (vec![8, 9], true, false),
+            ]
+        );
+        assert!(
+            extract_loops_to_render(&relation(11, &[]).1, &id_to_ways, &HashMap::new()).is_empty()
+        );
     }
 }
@@ -0,0 +1,355 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt, io,
+};
+
+use crate::{
+    classes::registry, stream::ElementReader, utils::way_classes, Bounds, Element, ElementId,
+    MemberType, Nd, Relation, Symbol, Tagged, Way,
+};
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Problem {
+    /// the reference is removed from the way
+    DanglingNodeRef { way: u64, node: u64 },
+    /// the member is removed from the relation
+    MissingMember { relation: u64, member: ElementId },
+    /// way without nodes, dropped
+    EmptyWay(u64),
+    /// way with less than two distinct nodes or area with less than three, dropped
+    DegenerateWay(u64),
+    /// area whose last node is not its first one, closed by repeating the first node
+    UnclosedArea(u64),
+    /// relation left without members, dropped
+    EmptyRelation(u64),
+}
+
+impl Problem {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Problem::DanglingNodeRef { .. } => "dangling node references",
+            Problem::MissingMember { .. } => "missing relation members",
+            Problem::EmptyWay(_) => "empty ways",
+            Problem::DegenerateWay(_) => "degenerate ways",
+            Problem::UnclosedArea(_) => "unclosed areas",
+            Problem::EmptyRelation(_) => "empty relations",
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::DanglingNodeRef { way, node } => {
+                write!(f, "way {} references missing node {}", way, node)
+            }
+            Problem::MissingMember { relation, member } => {
+                write!(f, "relation {} references missing {}", relation, member)
+            }
+            Problem::EmptyWay(way) => write!(f, "way {} has no nodes", way),
+            Problem::DegenerateWay(way) => write!(f, "way {} has too few distinct nodes", way),
+            Problem::UnclosedArea(way) => write!(f, "area way {} is not closed", way),
+            Problem::EmptyRelation(relation) => {
+                write!(f, "relation {} has no members left", relation)
+            }
+        }
+    }
+}
+
+/// number of problems of each kind
+pub fn summary(problems: &[Problem]) -> BTreeMap<&'static str, usize> {
+    problems.iter().fold(BTreeMap::new(), |mut acc, problem| {
+        *acc.entry(problem.kind()).or_default() += 1;
+        acc
+    })
+}
+
+/// ways that are filled when rendered
+pub fn is_area(way: &Way) -> bool {
+    way.has_tag(Symbol::AREA, Symbol::YES)
+        || way_classes(way)
+            .into_iter()
+            .any(|class| registry()[class].style.fill.is_some())
+}
+
+/// checks the referential integrity of a stream of elements ordered nodes, ways and relations, as
+/// in OSM exports, and repairs or drops the broken elements. Only the ids of the kept nodes and
+/// ways are held in memory, relations are held back until `finish` as they can reference
+/// relations that come later
+#[derive(Default)]
+pub struct Validator {
+    nodes: HashSet<u64>,
+    ways: HashSet<u64>,
+    relations: Vec<Relation>,
+    problems: Vec<Problem>,
+    seen_ways: bool,
+}
+
+fn out_of_order(element: &Element) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "{} comes after elements of a later type, the input has to be ordered nodes, ways, \
+             relations",
+            element.element_id()
+        ),
+    )
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Validator::default()
+    }
+
+    /// returns the element, repaired if needed, or `None` when it is dropped or held back
+    pub fn check(&mut self, element: Element) -> io::Result<Option<Element>> {
+        match element {
+            Element::Node(node) => {
+                if self.seen_ways || !self.relations.is_empty() {
+                    return Err(out_of_order(&Element::Node(node)));
+                }
+                self.nodes.insert(node.id);
+                Ok(Some(Element::Node(node)))
+            }
+            Element::Way(way) => {
+                if !self.relations.is_empty() {
+                    return Err(out_of_order(&Element::Way(way)));
+                }
+                self.seen_ways = true;
+                Ok(self.check_way(way).map(Element::Way))
+            }
+            Element::Relation(relation) => {
+                self.relations.push(relation);
+                Ok(None)
+            }
+        }
+    }
+
+    fn check_way(&mut self, mut way: Way) -> Option<Way> {
+        if way.nd.is_empty() {
+            self.problems.push(Problem::EmptyWay(way.id));
+            return None;
+        }
+
+        let (nodes, problems) = (&self.nodes, &mut self.problems);
+        way.nd.retain(|nd| {
+            let present = nodes.contains(&nd.reference);
+            if !present {
+                problems.push(Problem::DanglingNodeRef {
+                    way: way.id,
+                    node: nd.reference,
+                });
+            }
+            present
+        });
+
+        let distinct = way
+            .nd
+            .iter()
+            .map(|nd| nd.reference)
+            .collect::<HashSet<u64>>()
+            .len();
+        let area = is_area(&way);
+        if distinct < 2 || (area && distinct < 3) {
+            self.problems.push(if way.nd.is_empty() {
+                Problem::EmptyWay(way.id)
+            } else {
+                Problem::DegenerateWay(way.id)
+            });
+            return None;
+        }
+
+        let first = way.nd.first().unwrap().reference;
+        if area && way.nd.last().unwrap().reference != first {
+            self.problems.push(Problem::UnclosedArea(way.id));
+            way.nd.push(Nd { reference: first });
+        }
+
+        self.ways.insert(way.id);
+        Some(way)
+    }
+
+    /// repaired relations, in input order, and all the problems found
+    pub fn finish(mut self) -> (Vec<Relation>, Vec<Problem>) {
+        let mut relations: HashSet<u64> =
+            self.relations.iter().map(|relation| relation.id).collect();
+        // dropping a relation can leave the relations it is a member of empty, repeat until no
+        // relation is dropped
+        loop {
+            let mut dropped = false;
+            for relation in &mut self.relations {
+                if !relations.contains(&relation.id) {
+                    continue;
+                }
+                let (nodes, ways, problems) = (&self.nodes, &self.ways, &mut self.problems);
+                relation.member.retain(|member| {
+                    let present = match member.member_type {
+                        MemberType::Node => nodes.contains(&member.member_ref),
+                        MemberType::Way => ways.contains(&member.member_ref),
+                        MemberType::Relation => relations.contains(&member.member_ref),
+                    };
+                    if !present {
+                        problems.push(Problem::MissingMember {
+                            relation: relation.id,
+                            member: member.element_id(),
+                        });
+                    }
+                    present
+                });
+                if relation.member.is_empty() {
+                    self.problems.push(Problem::EmptyRelation(relation.id));
+                    relations.remove(&relation.id);
+                    dropped = true;
+                }
+            }
+            if !dropped {
+                break;
+            }
+        }
+
+        let relations = self
+            .relations
+            .into_iter()
+            .filter(|relation| relations.contains(&relation.id))
+            .collect();
+        (relations, self.problems)
+    }
+}
+
+/// validates the elements of a reader while they are streamed, the held back relations follow
+/// once the reader is exhausted
+pub struct ValidatingReader<R> {
+    reader: R,
+    validator: Option<Validator>,
+    relations: std::vec::IntoIter<Relation>,
+    problems: Vec<Problem>,
+}
+
+impl<R: ElementReader> ValidatingReader<R> {
+    pub fn new(reader: R) -> Self {
+        ValidatingReader {
+            reader,
+            validator: Some(Validator::new()),
+            relations: Vec::new().into_iter(),
+            problems: Vec::new(),
+        }
+    }
+
+    /// problems found, complete once all the elements have been read
+    pub fn problems(&self) -> &[Problem] {
+        &self.problems
+    }
+}
+
+impl<R: ElementReader> Iterator for ValidatingReader<R> {
+    type Item = io::Result<Element>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(validator) = self.validator.as_mut() {
+            match self.reader.next() {
+                Some(Ok(element)) => match validator.check(element) {
+                    Ok(Some(element)) => return Some(Ok(element)),
+                    Ok(None) => {}
+                    Err(error) => return Some(Err(error)),
+                },
+                Some(Err(error)) => return Some(Err(error)),
+                None => {
+                    let (relations, problems) = self.validator.take().unwrap().finish();
+                    self.relations = relations.into_iter();
+                    self.problems = problems;
+                }
+            }
+        }
+        self.relations
+            .next()
+            .map(|relation| Ok(Element::Relation(relation)))
+    }
+}
+
+impl<R: ElementReader> ElementReader for ValidatingReader<R> {
+    fn bounds(&self) -> Option<&Bounds> {
+        self.reader.bounds()
+    }
+
+    fn generator(&self) -> Option<&str> {
+        self.reader.generator()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{stream::XmlReader, Element, ElementId};
+
+    use super::{Problem, Validator};
+
+    const OSM_XML: &str = r#"<osm version="0.6">
+  <node id="1" lat="47.0" lon="28.8"/>
+  <node id="2" lat="47.1" lon="28.8"/>
+  <node id="3" lat="47.1" lon="28.9"/>
+  <way id="10"><nd ref="1"/><nd ref="9"/><nd ref="2"/></way>
+  <way id="11"><nd ref="1"/><nd ref="2"/><nd ref="3"/><tag k="leisure" v="park"/></way>
+  <way id="12"><nd ref="1"/><nd ref="2"/><nd ref="1"/><tag k="building" v="yes"/></way>
+  <way id="13"/>
+  <relation id="20">
+    <member type="way" ref="12" role="outer"/>
+    <member type="node" ref="8" role="label"/>
+  </relation>
+  <relation id="21">
+    <member type="relation" ref="20" role=""/>
+    <member type="way" ref="11" role=""/>
+  </relation>
+</osm>"#;
+
+    #[test]
+    fn repair_broken_references() {
+        let mut validator = Validator::new();
+        let elements: Vec<Element> = XmlReader::new(OSM_XML.as_bytes())
+            .flat_map(|element| validator.check(element.unwrap()).unwrap())
+            .collect();
+        let (relations, problems) = validator.finish();
+
+        assert_eq!(
+            problems,
+            vec![
+                Problem::DanglingNodeRef { way: 10, node: 9 },
+                Problem::UnclosedArea(11),
+                Problem::DegenerateWay(12),
+                Problem::EmptyWay(13),
+                Problem::MissingMember {
+                    relation: 20,
+                    member: ElementId::Way(12)
+                },
+                Problem::MissingMember {
+                    relation: 20,
+                    member: ElementId::Node(8)
+                },
+                Problem::EmptyRelation(20),
+                Problem::MissingMember {
+                    relation: 21,
+                    member: ElementId::Relation(20)
+                },
+            ]
+        );
+        let ways: Vec<(u64, usize)> = elements
+            .iter()
+            .flat_map(|element| match element {
+                Element::Way(way) => Some((way.id, way.nd.len())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ways, vec![(10, 2), (11, 4)]);
+        assert_eq!(relations.len(), 1);
+        assert_eq!(relations[0].member.len(), 1);
+    }
+
+    #[test]
+    fn reject_unordered_input() {
+        let mut validator = Validator::new();
+        let mut reader = XmlReader::new(
+            r#"<osm><way id="1"><nd ref="1"/></way><node id="1" lat="0" lon="0"/></osm>"#
+                .as_bytes(),
+        );
+        validator.check(reader.next().unwrap().unwrap()).unwrap();
+        assert!(validator.check(reader.next().unwrap().unwrap()).is_err());
+    }
+}